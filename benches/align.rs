@@ -0,0 +1,111 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use xrk_cli::export::{align_lap, align_nearest, AlignOptions, ChannelData, DataPoint, LapData};
+
+/// Timestamps of a channel sampled at `rate` Hz for `seconds`, with a little jitter.
+fn timestamps(rate: f64, seconds: f64) -> Vec<f64> {
+    let count = (rate * seconds) as usize;
+    (0..count)
+        .map(|i| (i as f64 + 0.1 * ((i % 7) as f64 / 7.0)) / rate)
+        .collect()
+}
+
+fn channel(name: &str, rate: f64, seconds: f64) -> ChannelData {
+    ChannelData {
+        name: name.to_string(),
+        unit: String::new(),
+        data: timestamps(rate, seconds)
+            .into_iter()
+            .map(|s| DataPoint { s, v: s.sin() })
+            .collect(),
+    }
+}
+
+/// The former nearest-neighbour alignment, scanning every channel sample for
+/// each master timestamp, to compare against.
+fn align_nearest_by_scan(
+    master_times: &[f64],
+    channel_times: &[f64],
+    channel_values: &[f64],
+) -> Vec<Option<f64>> {
+    master_times
+        .iter()
+        .map(|&master_time| {
+            channel_times
+                .iter()
+                .zip(channel_values.iter())
+                .min_by(|(time1, _), (time2, _)| {
+                    (*time1 - master_time)
+                        .abs()
+                        .partial_cmp(&(*time2 - master_time).abs())
+                        .unwrap()
+                })
+                .map(|(_, &value)| value)
+        })
+        .collect()
+}
+
+fn bench_align_nearest(c: &mut Criterion) {
+    let mut group = c.benchmark_group("align_nearest");
+    group.sample_size(20);
+
+    // A 20 Hz GPS master against a 1 kHz channel, for sessions of growing length.
+    for minutes in [1, 5, 20] {
+        let seconds = minutes as f64 * 60.0;
+        let master_times = timestamps(20.0, seconds);
+        let channel_times = timestamps(1000.0, seconds);
+        let channel_values: Vec<f64> = channel_times.iter().map(|t| t.sin()).collect();
+
+        group.bench_with_input(
+            BenchmarkId::new("1kHz", format!("{}min", minutes)),
+            &minutes,
+            |b, _| {
+                b.iter(|| {
+                    align_nearest(
+                        black_box(&master_times),
+                        black_box(&channel_times),
+                        black_box(&channel_values),
+                    )
+                })
+            },
+        );
+
+        // The scan takes too long to time on the longer sessions.
+        if minutes == 1 {
+            group.bench_with_input(
+                BenchmarkId::new("1kHz by scan", format!("{}min", minutes)),
+                &minutes,
+                |b, _| {
+                    b.iter(|| {
+                        align_nearest_by_scan(
+                            black_box(&master_times),
+                            black_box(&channel_times),
+                            black_box(&channel_values),
+                        )
+                    })
+                },
+            );
+        }
+    }
+
+    group.finish();
+}
+
+fn bench_align_lap(c: &mut Criterion) {
+    // A two minute lap with a typical mix of channel rates.
+    let seconds = 120.0;
+    let mut channels = vec![channel("ECEF position_X", 20.0, seconds)];
+    for (i, rate) in [1000.0, 500.0, 100.0, 100.0, 50.0, 20.0, 10.0, 5.0, 1.0]
+        .into_iter()
+        .enumerate()
+    {
+        channels.push(channel(&format!("Channel {}", i), rate, seconds));
+    }
+    let lap = LapData { lap: 0, channels };
+
+    c.bench_function("align_lap/10 channels/2min", |b| {
+        b.iter(|| align_lap(black_box(&lap), &AlignOptions::default()))
+    });
+}
+
+criterion_group!(benches, bench_align_nearest, bench_align_lap);
+criterion_main!(benches);
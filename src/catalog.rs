@@ -0,0 +1,114 @@
+use crate::source::{ChannelFamily, ChannelSamples, TelemetrySource};
+
+/// A single channel of a session, whatever family it belongs to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChannelEntry {
+    pub family: ChannelFamily,
+    /// Index of the channel within its family.
+    pub index: usize,
+    pub name: String,
+    pub unit: String,
+    pub sample_count: usize,
+    /// Estimated sample rate in Hz, rounded to the nearest integer.
+    pub rate: f64,
+}
+
+impl ChannelEntry {
+    /// All samples of this channel in the session.
+    pub fn samples(&self, source: &dyn TelemetrySource) -> ChannelSamples {
+        source
+            .channel_samples(self.family, self.index)
+            .unwrap_or_default()
+    }
+
+    /// Samples of this channel within a single lap.
+    pub fn lap_samples(&self, source: &dyn TelemetrySource, lap_index: usize) -> ChannelSamples {
+        source
+            .lap_channel_samples(self.family, lap_index, self.index)
+            .unwrap_or_default()
+    }
+
+    pub fn lap_sample_count(&self, source: &dyn TelemetrySource, lap_index: usize) -> usize {
+        source.lap_channel_samples_count(self.family, lap_index, self.index)
+    }
+}
+
+/// Every channel of a session: regular, GPS and GPS raw, in that order.
+#[derive(Debug, Clone, Default)]
+pub struct ChannelCatalog {
+    entries: Vec<ChannelEntry>,
+}
+
+impl ChannelCatalog {
+    pub fn build(source: &dyn TelemetrySource) -> Self {
+        let mut entries = Vec::new();
+
+        for family in ChannelFamily::ALL {
+            for index in 0..source.channels_count(family) {
+                let data = source.channel_samples(family, index).unwrap_or_default();
+
+                entries.push(ChannelEntry {
+                    family,
+                    index,
+                    name: source.channel_name(family, index).unwrap_or_default(),
+                    unit: source.channel_unit(family, index).unwrap_or_default(),
+                    sample_count: source.channel_samples_count(family, index),
+                    rate: estimate_rate(data.timestamps()),
+                });
+            }
+        }
+
+        Self { entries }
+    }
+
+    pub fn entries(&self) -> &[ChannelEntry] {
+        &self.entries
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, ChannelEntry> {
+        self.entries.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Looks up a channel by its exact name.
+    pub fn find(&self, name: &str) -> Option<&ChannelEntry> {
+        self.entries.iter().find(|entry| entry.name == name)
+    }
+
+    pub fn family(&self, family: ChannelFamily) -> impl Iterator<Item = &ChannelEntry> {
+        self.entries
+            .iter()
+            .filter(move |entry| entry.family == family)
+    }
+}
+
+impl<'a> IntoIterator for &'a ChannelCatalog {
+    type Item = &'a ChannelEntry;
+    type IntoIter = std::slice::Iter<'a, ChannelEntry>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.iter()
+    }
+}
+
+/// Estimates the sample rate from the average interval between timestamps.
+pub fn estimate_rate(timestamps: &[f64]) -> f64 {
+    if timestamps.len() < 2 {
+        return 0.0;
+    }
+
+    let intervals: Vec<f64> = timestamps
+        .windows(2)
+        .map(|pair| pair[1] - pair[0])
+        .collect();
+
+    let avg_interval = intervals.iter().sum::<f64>() / intervals.len() as f64;
+    (1.0 / avg_interval).round()
+}
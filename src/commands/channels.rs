@@ -1,154 +1,207 @@
-use xdrk::Run;
-
-pub fn display_channels_list(run: &Run, preview_enabled: bool) {
-    if preview_enabled {
-        println!(
-            "{:<20} {:<10} {:<20} {:<20} {:<30} {:<50}",
-            "CHANNEL".to_string(),
-            "UNIT".to_string(),
-            "COUNT".to_string(),
-            "FREQUENCY (Hz)".to_string(),
-            "PREVIEW (TIMESTAMPS)".to_string(),
-            "PREVIEW (DATA)".to_string()
-        );
-    } else {
-        println!(
-            "{:<20} {:<10} {:<20} {:<20}",
-            "CHANNEL".to_string(),
-            "UNIT".to_string(),
-            "COUNT".to_string(),
-            "FREQUENCY (Hz)".to_string()
-        );
+use super::output::{self, join_preview, OutputFormat, Report};
+use crate::catalog::{ChannelCatalog, ChannelEntry};
+use crate::error::Result;
+use crate::selection::LapSelection;
+use crate::source::{ChannelFamily, ChannelSamples, TelemetrySource};
+use serde::Serialize;
+use std::io::{self, Write};
+
+/// Number of samples shown in a preview.
+const PREVIEW_LEN: usize = 3;
+
+/// The first few samples of a channel.
+#[derive(Debug, Clone, Serialize)]
+pub struct Preview {
+    pub timestamps: Vec<f64>,
+    pub data: Vec<f64>,
+}
+
+impl Preview {
+    pub fn of(samples: &ChannelSamples) -> Self {
+        let len = samples.len().min(PREVIEW_LEN);
+        Self {
+            timestamps: samples.timestamps()[..len].to_vec(),
+            data: samples.samples()[..len].to_vec(),
+        }
     }
+}
 
-    for id in 0..run.channels_count() {
-        let channel_data = run
-            .channel_samples(id)
-            .unwrap_or_else(|_| xdrk::ChannelData::default());
+/// A channel of the catalog, optionally with a preview of its samples.
+#[derive(Debug, Clone, Serialize)]
+pub struct ChannelSummary {
+    pub name: String,
+    pub unit: String,
+    pub family: ChannelFamily,
+    pub count: usize,
+    /// Estimated sample rate in Hz.
+    pub rate: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preview: Option<Preview>,
+}
 
-        if preview_enabled {
-            let preview_data = &channel_data.samples()[..channel_data.samples().len().min(3)]
-                .iter()
-                .map(|&val| format!("{}", val))
-                .collect::<Vec<String>>()
-                .join(", ");
-
-            let preview_timestamps = &channel_data.timestamps()
-                [..channel_data.timestamps().len().min(3)]
-                .iter()
-                .map(|&val| format!("{:.3}", val))
-                .collect::<Vec<String>>()
-                .join(", ");
-
-            println!(
-                "{:<20} {:<10} {:<20} {:<20} {:<30} {:<50}",
-                run.channel_name(id).unwrap(),
-                run.channel_unit(id).unwrap(),
-                run.channel_samples_count(id).unwrap_or_else(|e| { 0 }),
-                calculate_frequency(&channel_data.timestamps()),
-                preview_timestamps,
-                preview_data
-            );
-        } else {
-            println!(
-                "{:<20} {:<10} {:<20} {:<20}",
-                run.channel_name(id).unwrap(),
-                run.channel_unit(id).unwrap(),
-                run.channel_samples_count(id).unwrap_or_else(|e| { 0 }),
-                calculate_frequency(&channel_data.timestamps())
-            );
+impl ChannelSummary {
+    pub(crate) fn new(entry: &ChannelEntry, count: usize, preview: Option<Preview>) -> Self {
+        Self {
+            name: entry.name.clone(),
+            unit: entry.unit.clone(),
+            family: entry.family,
+            count,
+            rate: entry.rate,
+            preview,
         }
     }
 
-    for id in 0..run.gps_channels_count() {
-        let channel_data = run
-            .channel_samples(id)
-            .unwrap_or_else(|_| xdrk::ChannelData::default());
-
-        if preview_enabled {
-            let preview_data = &channel_data.samples()[..channel_data.samples().len().min(3)]
-                .iter()
-                .map(|&val| format!("{}", val))
-                .collect::<Vec<String>>()
-                .join(", ");
-
-            let preview_timestamps = &channel_data.timestamps()
-                [..channel_data.timestamps().len().min(3)]
-                .iter()
-                .map(|&val| format!("{:.3}", val))
-                .collect::<Vec<String>>()
-                .join(", ");
-
-            println!(
-                "{:<20} {:<10} {:<20} {:<20} {:<30} {:<50}",
-                run.gps_channel_name(id).unwrap(),
-                run.gps_channel_unit(id).unwrap(),
-                run.gps_channel_samples_count(id).unwrap_or_else(|e| 0),
-                calculate_frequency(&channel_data.timestamps()),
-                preview_timestamps,
-                preview_data
-            );
-        } else {
-            println!(
-                "{:<20} {:<10} {:<20} {:<20}",
-                run.gps_channel_name(id).unwrap(),
-                run.gps_channel_unit(id).unwrap(),
-                run.gps_channel_samples_count(id).unwrap_or_else(|e| 0),
-                calculate_frequency(&channel_data.timestamps())
-            );
+    pub(crate) fn record(&self, lap: Option<usize>) -> ChannelRecord {
+        ChannelRecord {
+            lap,
+            name: self.name.clone(),
+            unit: self.unit.clone(),
+            family: self.family,
+            count: self.count,
+            rate: self.rate,
+            preview_timestamps: self
+                .preview
+                .as_ref()
+                .map(|preview| join_preview(&preview.timestamps, |v| v.to_string())),
+            preview_data: self
+                .preview
+                .as_ref()
+                .map(|preview| join_preview(&preview.data, |v| v.to_string())),
         }
     }
+}
 
-    for id in 0..run.gps_raw_channels_count() {
-        let channel_data = run
-            .gps_raw_channel_samples(id)
-            .unwrap_or_else(|_| xdrk::ChannelData::default()); // Will panic if there's an error
+/// Flat version of [`ChannelSummary`] for CSV output.
+#[derive(Debug, Clone, Serialize)]
+pub struct ChannelRecord {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lap: Option<usize>,
+    pub name: String,
+    pub unit: String,
+    pub family: ChannelFamily,
+    pub count: usize,
+    pub rate: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preview_timestamps: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preview_data: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(transparent)]
+pub struct ChannelList(pub Vec<ChannelSummary>);
+
+impl ChannelList {
+    /// Lists all channels; with `laps` (lap indices) the counts and previews
+    /// only cover those laps.
+    pub fn from_source(
+        source: &dyn TelemetrySource,
+        preview_enabled: bool,
+        laps: Option<&[usize]>,
+    ) -> Self {
+        let channels = ChannelCatalog::build(source)
+            .iter()
+            .map(|entry| match laps {
+                None => {
+                    let preview = preview_enabled.then(|| Preview::of(&entry.samples(source)));
+                    ChannelSummary::new(entry, entry.sample_count, preview)
+                }
+                Some(laps) => {
+                    let count = laps
+                        .iter()
+                        .map(|&lap| entry.lap_sample_count(source, lap))
+                        .sum();
+                    let preview = preview_enabled.then(|| {
+                        laps.first()
+                            .map(|&lap| Preview::of(&entry.lap_samples(source, lap)))
+                            .unwrap_or_else(|| Preview::of(&ChannelSamples::default()))
+                    });
+                    ChannelSummary::new(entry, count, preview)
+                }
+            })
+            .collect();
+
+        Self(channels)
+    }
+}
+
+impl Report for ChannelList {
+    type Record = ChannelRecord;
+
+    fn records(&self) -> Vec<ChannelRecord> {
+        self.0.iter().map(|channel| channel.record(None)).collect()
+    }
+
+    fn write_table(&self, out: &mut dyn Write) -> Result<()> {
+        let preview_enabled = self.0.iter().any(|channel| channel.preview.is_some());
 
         if preview_enabled {
-            let preview_data = &channel_data.samples()[..channel_data.samples().len().min(3)]
-                .iter()
-                .map(|&val| format!("{}", val))
-                .collect::<Vec<String>>()
-                .join(", ");
-
-            let preview_timestamps = &channel_data.timestamps()
-                [..channel_data.timestamps().len().min(3)]
-                .iter()
-                .map(|&val| format!("{:.3}", val))
-                .collect::<Vec<String>>()
-                .join(", ");
-
-            println!(
+            writeln!(
+                out,
                 "{:<20} {:<10} {:<20} {:<20} {:<30} {:<50}",
-                run.gps_raw_channel_name(id).unwrap(),
-                run.gps_raw_channel_unit(id).unwrap(),
-                run.gps_raw_channel_samples_count(id).unwrap_or_else(|e| 0),
-                calculate_frequency(&channel_data.timestamps()),
-                preview_timestamps,
-                preview_data
-            );
+                "CHANNEL",
+                "UNIT",
+                "COUNT",
+                "FREQUENCY (Hz)",
+                "PREVIEW (TIMESTAMPS)",
+                "PREVIEW (DATA)"
+            )?;
         } else {
-            println!(
+            writeln!(
+                out,
                 "{:<20} {:<10} {:<20} {:<20}",
-                run.gps_raw_channel_name(id).unwrap(),
-                run.gps_raw_channel_unit(id).unwrap(),
-                run.gps_raw_channel_samples_count(id).unwrap_or_else(|e| 0),
-                calculate_frequency(&channel_data.timestamps())
-            );
+                "CHANNEL", "UNIT", "COUNT", "FREQUENCY (Hz)"
+            )?;
         }
-    }
-}
 
-fn calculate_frequency(timestamps: &[f64]) -> f64 {
-    if timestamps.len() < 2 {
-        return 0.0;
+        for channel in &self.0 {
+            match &channel.preview {
+                Some(preview) => writeln!(
+                    out,
+                    "{:<20} {:<10} {:<20} {:<20} {:<30} {:<50}",
+                    channel.name,
+                    channel.unit,
+                    channel.count,
+                    channel.rate,
+                    join_preview(&preview.timestamps, |v| format!("{:.3}", v)),
+                    join_preview(&preview.data, |v| v.to_string())
+                )?,
+                None => writeln!(
+                    out,
+                    "{:<20} {:<10} {:<20} {:<20}",
+                    channel.name, channel.unit, channel.count, channel.rate
+                )?,
+            }
+        }
+
+        Ok(())
     }
+}
 
-    let intervals: Vec<f64> = timestamps
-        .windows(2)
-        .map(|pair| pair[1] - pair[0])
-        .collect();
+pub fn display_channels_list(
+    source: &dyn TelemetrySource,
+    preview_enabled: bool,
+    laps: Option<&LapSelection>,
+    format: OutputFormat,
+) -> Result<()> {
+    write_channels_list(
+        &mut io::stdout().lock(),
+        source,
+        preview_enabled,
+        laps,
+        format,
+    )
+}
 
-    let avg_interval = intervals.iter().sum::<f64>() / intervals.len() as f64;
-    (1.0 / avg_interval).round()
+pub fn write_channels_list(
+    out: &mut dyn Write,
+    source: &dyn TelemetrySource,
+    preview_enabled: bool,
+    laps: Option<&LapSelection>,
+    format: OutputFormat,
+) -> Result<()> {
+    let laps = laps.map(|laps| laps.select(source)).transpose()?;
+    let channels = ChannelList::from_source(source, preview_enabled, laps.as_deref());
+
+    output::render(out, format, &channels)
 }
@@ -1,235 +1,407 @@
-use csv::Writer;
-use serde::Serialize;
+use crate::catalog::{ChannelCatalog, ChannelEntry};
+use crate::error::{Error, Result};
+use crate::export::distance::{POSITION_CHANNEL_NAMES, SPEED_CHANNEL_NAME};
+use crate::export::{self, AlignOptions, ExportFormat, NdjsonRecord, TimeBase};
+use crate::selection::{self, ChannelSelection, LapSelection};
+use crate::source::TelemetrySource;
 use std::collections::HashSet;
-use xdrk::Run;
-
-#[derive(Serialize)]
-pub struct ExportData {
-    pub laps: Vec<LapData>,
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use tempfile::NamedTempFile;
+
+/// What to export and where to.
+#[derive(Debug, Clone, Default)]
+pub struct ExportOptions {
+    pub channels: ChannelSelection,
+    pub laps: LapSelection,
+    pub format: ExportFormat,
+    pub record: NdjsonRecord,
+    /// Resampling of the channels for CSV exports.
+    pub align: AlignOptions,
+    /// Output file, `-` for stdout; `export.<format>` if not set.
+    pub output: Option<PathBuf>,
+    /// Overwrite the output file if it exists.
+    pub force: bool,
 }
 
-#[derive(Serialize)]
-pub struct LapData {
-    pub lap: usize,
-    pub channels: Vec<ChannelData>,
-}
+/// Exports the data for a run.
+pub fn export(source: &dyn TelemetrySource, options: &ExportOptions) -> Result<()> {
+    let mut laps = options.laps.select(source)?;
+    // Laps without any time left, e.g. outside a time window, hold no data.
+    laps.retain(|&lap| source.lap_info(lap).is_some_and(|info| info.time() > 0.0));
+    if laps.is_empty() {
+        return Err(Error::InvalidArgument(
+            "none of the selected laps has data to export".to_string(),
+        ));
+    }
 
-#[derive(Serialize)]
-struct ChannelData {
-    name: String,
-    unit: String,
-    data: Vec<DataPoint>,
-}
+    let catalog = ChannelCatalog::build(source);
+    let selected = options.channels.select(&catalog)?;
+    if options.format.is_track() {
+        if let Some(missing) = POSITION_CHANNEL_NAMES
+            .into_iter()
+            .find(|&name| catalog.find(name).is_none())
+        {
+            return Err(Error::unknown_channel(missing));
+        }
+    }
+    // The master channel is looked up like the selected ones, then aligned
+    // on by its own name.
+    let mut align = options.align.clone();
+    if let TimeBase::Master(name) = &mut align.time_base {
+        if options.format.is_aligned() {
+            *name = selection::find_channel(&catalog, name)?.name.clone();
+        }
+    }
 
-#[derive(Serialize, Clone)]
-struct DataPoint {
-    s: f64,
-    v: f64,
-}
+    let output = options
+        .output
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(format!("export.{}", options.format.extension())));
+    let to_stdout = output == Path::new("-");
+    if options.format == ExportFormat::Motec && to_stdout {
+        return Err(Error::InvalidArgument(
+            "MoTeC exports are written to a file, not to stdout".to_string(),
+        ));
+    }
+    // A MoTeC log is a single stretch of time, so the laps cannot skip any.
+    if options.format == ExportFormat::Motec && laps.windows(2).any(|pair| pair[1] != pair[0] + 1) {
+        return Err(Error::InvalidArgument(
+            "MoTeC exports need consecutive laps (e.g. --laps 2-5)".to_string(),
+        ));
+    }
 
-// Data channels (sensors) come at various frequencies, so we attempt to align everything against a master channel.
-const MASTER_CHANNEL_NAME: &str = "ECEF position_X";
+    // Fail on existing files before collecting the data rather than after;
+    // the files only replace their destination once complete.
+    let mut pending = match to_stdout {
+        true => None,
+        false => Some(PendingOutput::create(&output, options.force)?),
+    };
+    // MoTeC keeps the lap markers in a second file next to the log.
+    let mut pending_ldx = match options.format {
+        ExportFormat::Motec => Some(PendingOutput::create(
+            &output.with_extension("ldx"),
+            options.force,
+        )?),
+        _ => None,
+    };
+    let writer: Box<dyn Write + Send + '_> = match pending.as_mut() {
+        Some(pending) => Box::new(&mut pending.file),
+        None => Box::new(io::stdout()),
+    };
+    let mut writer = BufWriter::new(writer);
 
-/// Aligns channel data to the master channel using nearest-neighbor interpolation.
-fn align_nearest(
-    master_times: &[f64],
-    channel_times: &[f64],
-    channel_values: &[f64],
-) -> Vec<Option<f64>> {
-    master_times
+    eprintln!(
+        "Preparing to export {} channel(s) for {} lap(s)",
+        if options.channels.is_all() {
+            "all".to_string()
+        } else {
+            selected.len().to_string()
+        },
+        laps.len()
+    );
+
+    // The channels of the time base are needed to align on, even when not
+    // asked for, but are then not written.
+    let mut channels: HashSet<&str> = HashSet::new();
+    if !options.channels.is_all() {
+        channels.extend(selected.iter().map(|entry| entry.name.as_str()));
+    }
+    if !channels.is_empty() && options.format.is_aligned() {
+        let time_base_only: Vec<String> = align
+            .time_base
+            .channels()
+            .into_iter()
+            .filter(|name| !channels.contains(name))
+            .map(String::from)
+            .collect();
+        align.time_base_only = time_base_only;
+        channels.extend(align.time_base_only.iter().map(String::as_str));
+    }
+    // Tracks are drawn from the GPS channels; the selected channels (or the
+    // speed) are the values at every point of a map.
+    let properties: Vec<String> = if options.channels.is_all() {
+        catalog
+            .find(SPEED_CHANNEL_NAME)
+            .map(|entry| entry.name.clone())
+            .into_iter()
+            .collect()
+    } else {
+        selected.iter().map(|entry| entry.name.clone()).collect()
+    };
+    if options.format.is_track() {
+        channels.extend(POSITION_CHANNEL_NAMES);
+        channels.insert(SPEED_CHANNEL_NAME);
+    }
+
+    let entries: Vec<&ChannelEntry> = catalog
         .iter()
-        .map(|&master_time| {
-            channel_times
-                .iter()
-                .zip(channel_values.iter())
-                .min_by(|(time1, _), (time2, _)| {
-                    (*time1 - master_time)
-                        .abs()
-                        .partial_cmp(&(*time2 - master_time).abs())
-                        .unwrap()
-                })
-                .map(|(_, &value)| value) // Use the nearest value
-        })
-        .collect()
-}
+        .filter(|entry| channels.is_empty() || channels.contains(entry.name.as_str()))
+        .collect();
+    eprintln!("Preparing channel data");
+    let export_data = export::collect(source, &entries, &laps);
+    match to_stdout {
+        true => eprintln!("Writing to stdout"),
+        false => eprintln!("Writing {}", output.display()),
+    }
 
-/// Exports the laps and channel data to a CSV file.
-fn export_to_csv(laps: &[LapData], file_path: &str) -> bool {
-    if let Ok(mut writer) = csv::Writer::from_path(file_path) {
-        eprintln!("Constructing csv header");
-
-        // Create the CSV header
-        let mut headers = vec!["lap (#)".to_string(), "time (s)".to_string()];
-        if let Some(first_lap) = laps.first() {
-            for channel in &first_lap.channels {
-                let header_value =
-                    (channel.name.clone() + " (" + &channel.unit.clone() + ")").to_string();
-                headers.push(header_value);
+    match options.format {
+        ExportFormat::Csv => export::write_csv(&export_data.laps, &align, &mut writer)?,
+        ExportFormat::Json => export::write_json(&export_data, &mut writer)?,
+        ExportFormat::Ndjson => {
+            export::write_ndjson(&export_data.laps, options.record, &mut writer)?
+        }
+        ExportFormat::Motec => {
+            export::write_ld(source, &export_data.laps, &mut writer)?;
+            if let Some(pending_ldx) = pending_ldx.as_mut() {
+                let mut ldx_writer = BufWriter::new(&mut pending_ldx.file);
+                export::write_ldx(source, &export_data.laps, &mut ldx_writer)?;
+                ldx_writer.flush()?;
             }
         }
-        let header_refs: Vec<&str> = headers.iter().map(String::as_str).collect();
-        if writer.write_record(&header_refs).is_err() {
-            return false;
+        ExportFormat::Gpx => export::write_gpx(
+            &export_data.laps,
+            source.track().as_deref(),
+            source.datetime(),
+            &mut writer,
+        )?,
+        ExportFormat::Geojson => {
+            export::write_geojson(&export_data.laps, &properties, &align, &mut writer)?
         }
+        ExportFormat::Kml => export::write_kml(
+            &export_data.laps,
+            &properties,
+            &align,
+            source.track().as_deref(),
+            source.datetime(),
+            &mut writer,
+        )?,
+        #[cfg(feature = "arrow")]
+        ExportFormat::Arrow => export::write_arrow(
+            &export_data.laps,
+            &align,
+            export::columnar::session_metadata(source, &laps),
+            // The file format is only complete once its footer is written.
+            if to_stdout {
+                export::IpcFormat::Stream
+            } else {
+                export::IpcFormat::File
+            },
+            &mut writer,
+        )?,
+        #[cfg(feature = "parquet")]
+        ExportFormat::Parquet => export::write_parquet(&export_data.laps, &align, &mut writer)?,
+    }
+    writer.flush()?;
+    drop(writer);
 
-        let mut row_counter = 0;
-
-        // Write data rows aligned to the master channel
-        for lap in laps {
-            eprintln!("Processing lap {}", lap.lap + 1);
-
-            // Find the master channel
-            let master_channel = lap
-                .channels
-                .iter()
-                .find(|channel| channel.name == MASTER_CHANNEL_NAME)
-                .expect("Master channel not found");
-
-            let master_times: Vec<f64> = master_channel
-                .data
-                .iter()
-                .map(|data_point| data_point.s)
-                .collect();
-
-            // Align all channels to the master channel
-            let mut aligned_data: Vec<Option<Vec<f64>>> = vec![None; lap.channels.len()];
-
-            for (i, channel) in lap.channels.iter().enumerate() {
-                if channel.name == MASTER_CHANNEL_NAME {
-                    // Add the master channel directly
-                    aligned_data[i] = Some(master_channel.data.iter().map(|dp| dp.v).collect());
-                    continue;
-                }
-
-                println!("Aligning datapoints for channel {}", channel.name);
-
-                let channel_times: Vec<f64> = channel.data.iter().map(|dp| dp.s).collect();
-                let channel_values: Vec<f64> = channel.data.iter().map(|dp| dp.v).collect();
-                let aligned_values = align_nearest(&master_times, &channel_times, &channel_values);
-                aligned_data[i] = Some(aligned_values.into_iter().flatten().collect());
-            }
+    for pending in [pending, pending_ldx].into_iter().flatten() {
+        pending.persist()?;
+    }
+    eprintln!("Export created successfully");
 
-            println!("Writing datapoints to file");
-            for (i, &master_time) in master_times.iter().enumerate() {
-                let mut row = vec![(lap.lap + 1).to_string(), format!("{:.3}", master_time)];
-
-                for aligned_channel in &aligned_data {
-                    if let Some(values) = aligned_channel {
-                        if let Some(value) = values.get(i) {
-                            row.push(value.to_string());
-                        } else {
-                            row.push(String::new()); // Missing values
-                        }
-                    } else {
-                        row.push(String::new()); // Missing channel
-                    }
-                }
-
-                if writer.write_record(&row).is_err() {
-                    return false;
-                } else {
-                    row_counter += 1;
-                }
-            }
-        }
+    Ok(())
+}
 
-        eprintln!("Created {} rows", row_counter);
+/// An output file written under a temporary name next to its destination,
+/// so that a failed export leaves nothing behind.
+struct PendingOutput {
+    file: NamedTempFile,
+    path: PathBuf,
+    force: bool,
+}
 
-        if writer.flush().is_err() {
-            return false;
+impl PendingOutput {
+    fn create(path: &Path, force: bool) -> Result<Self> {
+        if !force && path.exists() {
+            return Err(Error::OutputExists(path.to_path_buf()));
         }
 
-        true
-    } else {
-        false
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let mut builder = tempfile::Builder::new();
+        builder.prefix(".xrk-cli-");
+        // The usual permissions of a new file rather than those of a temporary one.
+        #[cfg(unix)]
+        builder.permissions(std::os::unix::fs::PermissionsExt::from_mode(0o666));
+        let file = builder.tempfile_in(dir).map_err(Error::Write)?;
+
+        Ok(Self {
+            file,
+            path: path.to_path_buf(),
+            force,
+        })
     }
-}
 
-/// Exports the data for a run to a CSV file.
-pub fn export(run: &Run, desired_channels: Option<HashSet<&str>>) {
-    let mut laps: Vec<LapData> = Vec::new();
-    let desired_channels = desired_channels.unwrap_or_default();
+    /// Moves the complete file to its destination.
+    fn persist(self) -> Result<()> {
+        let Self { file, path, force } = self;
+        let persisted = match force {
+            true => file.persist(&path),
+            false => file.persist_noclobber(&path),
+        };
+
+        persisted.map(|_| ()).map_err(|err| match err.error.kind() {
+            io::ErrorKind::AlreadyExists => Error::OutputExists(path),
+            _ => Error::Write(err.error),
+        })
+    }
+}
 
-    eprintln!(
-        "Preparing to export {} channel(s) per lap",
-        if desired_channels.is_empty() {
-            "all".to_string()
-        } else {
-            desired_channels.len().to_string()
-        }
-    );
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::{ChannelFamily, MemorySource, TimeWindow, WindowedSource};
+
+    fn session() -> MemorySource {
+        MemorySource::new().with_lap(0.0, 1.0).with_channel(
+            ChannelFamily::Regular,
+            "RPM",
+            "rpm",
+            vec![0.0, 0.5],
+            vec![3000.0, 3100.0],
+        )
+    }
 
-    for lap_index in 0..run.number_of_laps() {
-        eprintln!("Preparing channel data for lap {}", lap_index + 1);
+    #[test]
+    fn existing_output_is_only_overwritten_when_forced() {
+        let existing = tempfile::NamedTempFile::new().unwrap();
 
-        let mut channel_data_list: Vec<ChannelData> = Vec::new();
+        assert!(matches!(
+            PendingOutput::create(existing.path(), false),
+            Err(Error::OutputExists(_))
+        ));
 
-        // Process regular channels
-        for channel_id in 0..run.channels_count() {
-            let channel_name = run.channel_name(channel_id).unwrap_or_default();
-            if desired_channels.len() > 0 && !desired_channels.contains(channel_name.as_str()) {
-                continue;
-            }
+        let mut pending = PendingOutput::create(existing.path(), true).unwrap();
+        pending.file.write_all(b"new").unwrap();
+        pending.persist().unwrap();
+        assert_eq!(std::fs::read(existing.path()).unwrap(), b"new");
+    }
 
-            let channel_data = run
-                .lap_channel_samples(lap_index, channel_id)
-                .unwrap_or_else(|_| xdrk::ChannelData::default());
-
-            let data_points = channel_data
-                .timestamps()
-                .iter()
-                .zip(channel_data.samples().iter())
-                .map(|(&seconds, &value)| DataPoint {
-                    s: seconds as f64,
-                    v: value as f64,
-                })
-                .collect();
-
-            channel_data_list.push(ChannelData {
-                name: channel_name,
-                unit: run.channel_unit(channel_id).unwrap_or_default(),
-                data: data_points,
-            });
-        }
+    #[test]
+    fn failed_export_leaves_no_file_behind() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("export.csv");
+        let options = |master: &str| ExportOptions {
+            align: AlignOptions {
+                time_base: TimeBase::Master(master.to_string()),
+                ..AlignOptions::default()
+            },
+            output: Some(output.clone()),
+            ..ExportOptions::default()
+        };
+
+        assert!(matches!(
+            export(&session(), &options("Gear")),
+            Err(Error::UnknownChannel { .. })
+        ));
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+
+        // Any case of the master channel name will do.
+        export(&session(), &options("rpm")).unwrap();
+        let csv = std::fs::read_to_string(&output).unwrap();
+        assert_eq!(csv.lines().count(), 3, "{}", csv);
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
 
-        // Process GPS RAW channels
-        for gps_channel_id in 0..run.gps_raw_channels_count() {
-            let channel_name = run.gps_raw_channel_name(gps_channel_id).unwrap_or_default();
-            if desired_channels.len() > 0 && !desired_channels.contains(channel_name.as_str()) {
-                continue;
-            }
+    #[test]
+    fn window_of_a_session_without_laps_is_exported() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("export.csv");
+        let source = MemorySource::new().with_channel(
+            ChannelFamily::Regular,
+            "RPM",
+            "rpm",
+            vec![0.0, 0.5, 1.0, 1.5],
+            vec![3000.0, 3100.0, 3200.0, 3300.0],
+        );
+        let windowed = WindowedSource::new(
+            Box::new(source),
+            TimeWindow::new(Some(0.5), Some(1.5)).unwrap(),
+        );
+
+        let options = ExportOptions {
+            output: Some(output.clone()),
+            ..ExportOptions::default()
+        };
+        export(&windowed, &options).unwrap();
+
+        let csv = std::fs::read_to_string(&output).unwrap();
+        assert_eq!(csv.lines().count(), 3, "{}", csv);
+        assert!(csv.contains("3100"), "{}", csv);
+    }
 
-            let channel_data = run
-                .lap_gps_raw_channel_samples(lap_index, gps_channel_id)
-                .unwrap_or_else(|_| xdrk::ChannelData::default());
-
-            let data_points = channel_data
-                .timestamps()
-                .iter()
-                .zip(channel_data.samples().iter())
-                .map(|(&seconds, &value)| DataPoint {
-                    s: seconds as f64,
-                    v: value as f64,
-                })
-                .collect();
-
-            channel_data_list.push(ChannelData {
-                name: channel_name,
-                unit: run.gps_raw_channel_unit(gps_channel_id).unwrap_or_default(),
-                data: data_points,
-            });
-        }
+    #[test]
+    fn motec_exports_reject_laps_with_gaps() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = session().with_lap(1.0, 1.0).with_lap(2.0, 1.0);
+        let options = ExportOptions {
+            laps: "1,3".parse().unwrap(),
+            format: ExportFormat::Motec,
+            output: Some(dir.path().join("export.ld")),
+            ..ExportOptions::default()
+        };
+
+        assert!(matches!(
+            export(&source, &options),
+            Err(Error::InvalidArgument(_))
+        ));
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
 
-        // Store the lap data
-        laps.push(LapData {
-            lap: lap_index,
-            channels: channel_data_list,
-        });
+    #[test]
+    fn laps_without_data_in_the_window_are_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let windowed = WindowedSource::new(
+            Box::new(session().with_lap(1.0, 1.0)),
+            TimeWindow::new(Some(5.0), None).unwrap(),
+        );
+        let options = ExportOptions {
+            output: Some(dir.path().join("export.csv")),
+            ..ExportOptions::default()
+        };
+
+        assert!(matches!(
+            export(&windowed, &options),
+            Err(Error::InvalidArgument(_))
+        ));
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
     }
 
-    if export_to_csv(&laps, "export.csv") {
-        eprintln!("Export created successfully");
-    } else {
-        eprintln!("Failed to create export");
+    #[test]
+    fn time_base_channels_are_only_written_when_selected() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("export.csv");
+        let source = session().with_channel(
+            ChannelFamily::Regular,
+            "Gear",
+            "",
+            vec![0.0, 0.25, 0.5],
+            vec![1.0, 2.0, 2.0],
+        );
+        let options = |channels: &str| ExportOptions {
+            channels: ChannelSelection::parse(channels, &Default::default()).unwrap(),
+            align: AlignOptions {
+                time_base: TimeBase::Master("gear".to_string()),
+                ..AlignOptions::default()
+            },
+            output: Some(output.clone()),
+            force: true,
+            ..ExportOptions::default()
+        };
+
+        export(&source, &options("rpm")).unwrap();
+        let csv = std::fs::read_to_string(&output).unwrap();
+        assert_eq!(csv.lines().next(), Some("lap (#),time (s),RPM (rpm)"));
+        assert_eq!(csv.lines().count(), 4, "{}", csv);
+
+        export(&source, &options("rpm,gear")).unwrap();
+        let csv = std::fs::read_to_string(&output).unwrap();
+        assert_eq!(
+            csv.lines().next(),
+            Some("lap (#),time (s),RPM (rpm),Gear ()")
+        );
     }
 }
@@ -1,48 +1,104 @@
-use xdrk::Run;
-
-pub fn display_run_info(run: &Run) {
-    println!(
-        "{:<30}: {:?}",
-        "DATETIME".to_string(),
-        run.datetime().unwrap()
-    );
-
-    println!("=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=");
-
-    println!(
-        "{:<30}: {}",
-        "DRIVER".to_string(),
-        run.racer().unwrap_or("Unknown".to_string())
-    );
-    println!(
-        "{:<30}: {}",
-        "VEHICLE".to_string(),
-        run.vehicle().unwrap_or("Unknown".to_string())
-    );
-    println!(
-        "{:<30}: {}",
-        "TRACK".to_string(),
-        run.track().unwrap_or("Unknown".to_string())
-    );
-    println!(
-        "{:<30}: {} / {}",
-        "CHAMPIONSHIP".to_string(),
-        run.championship().unwrap_or("Unknown".to_string()),
-        run.venue_type().unwrap_or("Unknown".to_string())
-    );
-    println!("{:<30}: {}", "LAPS".to_string(), run.number_of_laps());
-
-    println!("=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=");
-
-    println!(
-        "{:<30}: {:?}",
-        "DATA CHANNELS".to_string(),
-        run.channels_count()
-    );
-    println!(
-        "{:<30}: {:?} (+{:?} raw)",
-        "GPS DATA CHANNELS".to_string(),
-        run.gps_channels_count(),
-        run.gps_raw_channels_count()
-    );
+use super::output::{self, OutputFormat, Report};
+use crate::error::Result;
+use crate::source::{ChannelFamily, TelemetrySource};
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use std::io::{self, Write};
+
+/// Session metadata and channel counts.
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionInfo {
+    pub datetime: Option<NaiveDateTime>,
+    pub driver: Option<String>,
+    pub vehicle: Option<String>,
+    pub track: Option<String>,
+    pub championship: Option<String>,
+    pub venue_type: Option<String>,
+    pub laps: usize,
+    pub data_channels: usize,
+    pub gps_channels: usize,
+    pub gps_raw_channels: usize,
+}
+
+impl SessionInfo {
+    pub fn from_source(source: &dyn TelemetrySource) -> Self {
+        Self {
+            datetime: source.datetime(),
+            driver: source.racer(),
+            vehicle: source.vehicle(),
+            track: source.track(),
+            championship: source.championship(),
+            venue_type: source.venue_type(),
+            laps: source.number_of_laps(),
+            data_channels: source.channels_count(ChannelFamily::Regular),
+            gps_channels: source.channels_count(ChannelFamily::Gps),
+            gps_raw_channels: source.channels_count(ChannelFamily::GpsRaw),
+        }
+    }
+}
+
+fn or_unknown(value: &Option<String>) -> &str {
+    value.as_deref().unwrap_or("Unknown")
+}
+
+impl Report for SessionInfo {
+    type Record = SessionInfo;
+
+    fn records(&self) -> Vec<SessionInfo> {
+        vec![self.clone()]
+    }
+
+    fn write_table(&self, out: &mut dyn Write) -> Result<()> {
+        writeln!(
+            out,
+            "{:<30}: {}",
+            "DATETIME",
+            self.datetime
+                .map(|datetime| format!("{:?}", datetime))
+                .unwrap_or("Unknown".to_string())
+        )?;
+
+        writeln!(
+            out,
+            "=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-="
+        )?;
+
+        writeln!(out, "{:<30}: {}", "DRIVER", or_unknown(&self.driver))?;
+        writeln!(out, "{:<30}: {}", "VEHICLE", or_unknown(&self.vehicle))?;
+        writeln!(out, "{:<30}: {}", "TRACK", or_unknown(&self.track))?;
+        writeln!(
+            out,
+            "{:<30}: {} / {}",
+            "CHAMPIONSHIP",
+            or_unknown(&self.championship),
+            or_unknown(&self.venue_type)
+        )?;
+        writeln!(out, "{:<30}: {}", "LAPS", self.laps)?;
+
+        writeln!(
+            out,
+            "=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-="
+        )?;
+
+        writeln!(out, "{:<30}: {:?}", "DATA CHANNELS", self.data_channels)?;
+        writeln!(
+            out,
+            "{:<30}: {:?} (+{:?} raw)",
+            "GPS DATA CHANNELS", self.gps_channels, self.gps_raw_channels
+        )?;
+
+        Ok(())
+    }
+}
+
+pub fn display_run_info(source: &dyn TelemetrySource, format: OutputFormat) -> Result<()> {
+    write_run_info(&mut io::stdout().lock(), source, format)
+}
+
+pub fn write_run_info(
+    out: &mut dyn Write,
+    source: &dyn TelemetrySource,
+    format: OutputFormat,
+) -> Result<()> {
+    output::render(out, format, &SessionInfo::from_source(source))
 }
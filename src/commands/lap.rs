@@ -1,110 +1,124 @@
-use xdrk::Run;
+use super::channels::{ChannelRecord, ChannelSummary, Preview};
+use super::output::{self, join_preview, OutputFormat, Report};
+use crate::catalog::ChannelCatalog;
+use crate::error::{Error, Result};
+use crate::selection::LapSelection;
+use crate::source::TelemetrySource;
+use serde::Serialize;
+use std::io::{self, Write};
 
-pub fn display_run_info(run: &Run) {
-    println!(
-        "{:<5} {:<20} {:<10} {:<20} {:<60} {:<50}",
-        "LAP".to_string(),
-        "CHANNEL".to_string(),
-        "UNIT".to_string(),
-        "COUNT".to_string(),
-        "PREVIEW (TIMESTAMPS)".to_string(),
-        "PREVIEW (DATA)".to_string()
-    );
-
-    // TODO: obviously, this should be a cmdline arg
-    let lap = 3;
-    let lap_index = lap - 1;
-
-    // Regular channels
-    for id in 0..run.channels_count() {
-        let channel_data = run
-            .lap_channel_samples(lap_index, id)
-            .unwrap_or_else(|_| xdrk::ChannelData::default());
+/// Preview of every channel within a single lap.
+#[derive(Debug, Clone, Serialize)]
+pub struct LapPreview {
+    pub lap: usize,
+    pub channels: Vec<ChannelSummary>,
+}
 
-        let preview_data = &channel_data.samples()[..channel_data.samples().len().min(3)]
-            .iter()
-            .map(|&val| format!("{}", val))
-            .collect::<Vec<String>>()
-            .join(", ");
+impl LapPreview {
+    /// Builds the preview of lap number `lap` (starting at 1) for the channels
+    /// of `catalog`.
+    pub fn from_source(
+        source: &dyn TelemetrySource,
+        catalog: &ChannelCatalog,
+        lap: usize,
+    ) -> Result<Self> {
+        let lap_index = lap.checked_sub(1).ok_or(Error::UnknownLap(lap))?;
+        if lap_index >= source.number_of_laps() {
+            return Err(Error::UnknownLap(lap));
+        }
 
-        let preview_timestamps = &channel_data.timestamps()
-            [..channel_data.timestamps().len().min(3)]
+        let channels = catalog
             .iter()
-            .map(|&val| format!("{}", val))
-            .collect::<Vec<String>>()
-            .join(", ");
+            .map(|entry| {
+                let preview = Preview::of(&entry.lap_samples(source, lap_index));
+                ChannelSummary::new(
+                    entry,
+                    entry.lap_sample_count(source, lap_index),
+                    Some(preview),
+                )
+            })
+            .collect();
 
-        println!(
-            "{:<5} {:<20} {:<10} {:<20} {:<60} {:<50}",
-            lap,
-            run.channel_name(id).unwrap(),
-            run.channel_unit(id).unwrap(),
-            run.lap_channel_samples_count(lap_index, id)
-                .unwrap_or_else(|e| { 0 }),
-            preview_timestamps,
-            preview_data
-        );
+        Ok(Self { lap, channels })
     }
+}
 
-    // GPS channels
-    for id in 0..run.gps_channels_count() {
-        let channel_data = run
-            .lap_channel_samples(lap_index, id)
-            .unwrap_or_else(|_| xdrk::ChannelData::default());
-
-        let preview_data = &channel_data.samples()[..channel_data.samples().len().min(3)]
-            .iter()
-            .map(|&val| format!("{}", val))
-            .collect::<Vec<String>>()
-            .join(", ");
+/// Previews of a selection of laps.
+#[derive(Debug, Clone, Serialize)]
+#[serde(transparent)]
+pub struct LapPreviewList(pub Vec<LapPreview>);
 
-        let preview_timestamps = &channel_data.timestamps()
-            [..channel_data.timestamps().len().min(3)]
-            .iter()
-            .map(|&val| format!("{}", val))
-            .collect::<Vec<String>>()
-            .join(", ");
+impl LapPreviewList {
+    pub fn from_source(source: &dyn TelemetrySource, laps: &LapSelection) -> Result<Self> {
+        let laps = laps.select(source)?;
+        let catalog = ChannelCatalog::build(source);
 
-        println!(
-            "{:<5} {:<20} {:<10} {:<20} {:<60} {:<50}",
-            lap,
-            run.gps_channel_name(id).unwrap(),
-            run.gps_channel_unit(id).unwrap(),
-            run.lap_gps_channel_samples_count(lap_index, id)
-                .unwrap_or_else(|e| 0),
-            preview_timestamps,
-            preview_data
-        );
+        laps.into_iter()
+            .map(|lap_index| LapPreview::from_source(source, &catalog, lap_index + 1))
+            .collect::<Result<Vec<LapPreview>>>()
+            .map(Self)
     }
+}
 
-    // GPS Raw channels
-    for id in 0..run.gps_raw_channels_count() {
-        let channel_data = run
-            .lap_gps_raw_channel_samples(lap_index, id)
-            .unwrap_or_else(|_| xdrk::ChannelData::default()); // Will panic if there's an error
-
-        let preview_data = &channel_data.samples()[..channel_data.samples().len().min(3)]
-            .iter()
-            .map(|&val| format!("{}", val))
-            .collect::<Vec<String>>()
-            .join(", ");
+impl Report for LapPreviewList {
+    type Record = ChannelRecord;
 
-        let preview_timestamps = &channel_data.timestamps()
-            [..channel_data.timestamps().len().min(3)]
+    fn records(&self) -> Vec<ChannelRecord> {
+        self.0
             .iter()
-            .map(|&val| format!("{}", val))
-            .collect::<Vec<String>>()
-            .join(", ");
+            .flat_map(|lap| {
+                lap.channels
+                    .iter()
+                    .map(|channel| channel.record(Some(lap.lap)))
+            })
+            .collect()
+    }
 
-        println!(
+    fn write_table(&self, out: &mut dyn Write) -> Result<()> {
+        writeln!(
+            out,
             "{:<5} {:<20} {:<10} {:<20} {:<60} {:<50}",
-            lap,
-            run.gps_raw_channel_name(id).unwrap(),
-            run.gps_raw_channel_unit(id).unwrap(),
-            run.lap_gps_raw_channel_samples_count(lap_index, id)
-                .unwrap_or_else(|e| 0),
-            preview_timestamps,
-            preview_data
-        );
+            "LAP", "CHANNEL", "UNIT", "COUNT", "PREVIEW (TIMESTAMPS)", "PREVIEW (DATA)"
+        )?;
+
+        for lap in &self.0 {
+            for channel in &lap.channels {
+                let (timestamps, data) = channel
+                    .preview
+                    .as_ref()
+                    .map(|preview| {
+                        (
+                            join_preview(&preview.timestamps, |v| v.to_string()),
+                            join_preview(&preview.data, |v| v.to_string()),
+                        )
+                    })
+                    .unwrap_or_default();
+
+                writeln!(
+                    out,
+                    "{:<5} {:<20} {:<10} {:<20} {:<60} {:<50}",
+                    lap.lap, channel.name, channel.unit, channel.count, timestamps, data
+                )?;
+            }
+        }
+
+        Ok(())
     }
 }
+
+pub fn display_run_info(
+    source: &dyn TelemetrySource,
+    laps: &LapSelection,
+    format: OutputFormat,
+) -> Result<()> {
+    write_run_info(&mut io::stdout().lock(), source, laps, format)
+}
+
+pub fn write_run_info(
+    out: &mut dyn Write,
+    source: &dyn TelemetrySource,
+    laps: &LapSelection,
+    format: OutputFormat,
+) -> Result<()> {
+    output::render(out, format, &LapPreviewList::from_source(source, laps)?)
+}
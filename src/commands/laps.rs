@@ -1,36 +1,81 @@
-use xdrk::Run;
-
-pub fn display_laps_info(run: &Run) {
-    println!(
-        "{:<10} {:<20} {:<20} {}",
-        "LAP".to_string(),
-        "START".to_string(),
-        "DURATION".to_string(),
-        "LAP TIME".to_string()
-    );
-
-    for lap in 0..run.number_of_laps() {
-        let info = run.lap_info(lap);
-        match info {
-            Ok(lap_info) => {
-                let start_time = format!("{:.3}", lap_info.start());
-                let duration = format!("{:.3}", lap_info.time());
-
-                let minutes = (&lap_info.time() / 60.0).floor();
-                let secs = &lap_info.time() % 60.0;
-                let lap_time = format!("{:02}:{:06.3}", minutes as u64, secs);
-
-                println!(
-                    "{:<10} {:<20} {:<20} {}",
-                    lap_info.number(),
-                    start_time,
-                    duration,
-                    lap_time
-                );
-            }
-            Err(e) => {
-                continue;
-            }
+use super::output::{self, OutputFormat, Report};
+use crate::error::Result;
+use crate::source::TelemetrySource;
+use serde::Serialize;
+use std::io::{self, Write};
+
+/// Timing of a single lap; `start` and `duration` are in seconds.
+#[derive(Debug, Clone, Serialize)]
+pub struct LapSummary {
+    pub lap: usize,
+    pub start: f64,
+    pub duration: f64,
+    /// Lap time formatted as `mm:ss.sss`.
+    pub lap_time: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(transparent)]
+pub struct LapList(pub Vec<LapSummary>);
+
+impl LapList {
+    pub fn from_source(source: &dyn TelemetrySource) -> Self {
+        let laps = (0..source.number_of_laps())
+            .filter_map(|lap| source.lap_info(lap))
+            .map(|lap_info| {
+                let minutes = (lap_info.time() / 60.0).floor();
+                let secs = lap_info.time() % 60.0;
+
+                LapSummary {
+                    lap: lap_info.number(),
+                    start: lap_info.start(),
+                    duration: lap_info.time(),
+                    lap_time: format!("{:02}:{:06.3}", minutes as u64, secs),
+                }
+            })
+            .collect();
+
+        Self(laps)
+    }
+}
+
+impl Report for LapList {
+    type Record = LapSummary;
+
+    fn records(&self) -> Vec<LapSummary> {
+        self.0.clone()
+    }
+
+    fn write_table(&self, out: &mut dyn Write) -> Result<()> {
+        writeln!(
+            out,
+            "{:<10} {:<20} {:<20} LAP TIME",
+            "LAP", "START", "DURATION"
+        )?;
+
+        for lap in &self.0 {
+            writeln!(
+                out,
+                "{:<10} {:<20} {:<20} {}",
+                lap.lap,
+                format!("{:.3}", lap.start),
+                format!("{:.3}", lap.duration),
+                lap.lap_time
+            )?;
         }
+
+        Ok(())
     }
 }
+
+pub fn display_laps_info(source: &dyn TelemetrySource, format: OutputFormat) -> Result<()> {
+    write_laps_info(&mut io::stdout().lock(), source, format)
+}
+
+pub fn write_laps_info(
+    out: &mut dyn Write,
+    source: &dyn TelemetrySource,
+    format: OutputFormat,
+) -> Result<()> {
+    output::render(out, format, &LapList::from_source(source))
+}
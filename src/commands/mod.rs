@@ -3,3 +3,208 @@ pub mod export;
 pub mod info;
 pub mod lap;
 pub mod laps;
+pub mod output;
+
+#[cfg(test)]
+mod tests {
+    use super::output::OutputFormat;
+    use crate::selection::LapSelection;
+    use crate::source::{ChannelFamily, MemorySource};
+    use chrono::NaiveDate;
+
+    fn session() -> MemorySource {
+        let timestamps: Vec<f64> = (0..300).map(|i| i as f64 / 10.0).collect();
+
+        MemorySource::new()
+            .with_championship("Club")
+            .with_track("Zandvoort")
+            .with_venue_type("Circuit")
+            .with_vehicle("Clio")
+            .with_racer("Driver")
+            .with_datetime(
+                NaiveDate::from_ymd_opt(2024, 5, 1)
+                    .unwrap()
+                    .and_hms_opt(14, 30, 0)
+                    .unwrap(),
+            )
+            .with_lap(0.0, 10.0)
+            .with_lap(10.0, 10.0)
+            .with_lap(20.0, 10.0)
+            .with_channel(
+                ChannelFamily::Regular,
+                "RPM",
+                "rpm",
+                timestamps.clone(),
+                timestamps.iter().map(|t| 3000.0 + t * 10.0).collect(),
+            )
+            .with_channel(
+                ChannelFamily::Gps,
+                "GPS Speed",
+                "km/h",
+                timestamps.clone(),
+                timestamps.iter().map(|t| t * 5.0).collect(),
+            )
+            .with_channel(
+                ChannelFamily::GpsRaw,
+                "ECEF position_X",
+                "m",
+                timestamps.clone(),
+                timestamps.iter().map(|t| 3_900_000.0 + t).collect(),
+            )
+    }
+
+    #[test]
+    fn commands_run_against_synthetic_session() {
+        let source = session();
+        let render = |write: &dyn Fn(&mut Vec<u8>) -> crate::Result<()>| {
+            let mut output = Vec::new();
+            write(&mut output).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+
+        let info = render(&|out| super::info::write_run_info(out, &source, OutputFormat::Table));
+        for expected in [
+            "2024-05-01T14:30:00",
+            "Driver",
+            "Clio",
+            "Zandvoort",
+            "Club / Circuit",
+        ] {
+            assert!(info.contains(expected), "no {} in:\n{}", expected, info);
+        }
+        assert!(info.contains(&format!("{:<30}: 3", "LAPS")), "{}", info);
+
+        let laps = render(&|out| super::laps::write_laps_info(out, &source, OutputFormat::Table));
+        let lines: Vec<&str> = laps.lines().collect();
+        assert_eq!(lines.len(), 4, "{}", laps);
+        assert!(lines[3].starts_with("3 "), "{}", laps);
+        assert!(lines[3].ends_with("00:10.000"), "{}", laps);
+
+        let channels = render(&|out| {
+            super::channels::write_channels_list(out, &source, true, None, OutputFormat::Table)
+        });
+        for name in ["RPM", "GPS Speed", "ECEF position_X"] {
+            assert!(channels.contains(name), "no {} in:\n{}", name, channels);
+        }
+
+        let lap = render(&|out| {
+            super::lap::write_run_info(out, &source, &LapSelection::default(), OutputFormat::Table)
+        });
+        for lap_number in 1..=3 {
+            let line = output_line(lap.as_bytes(), &format!("{:<5} RPM", lap_number));
+            assert!(line.contains("rpm"), "{}", line);
+        }
+    }
+
+    fn output_line(output: &[u8], channel: &str) -> String {
+        String::from_utf8_lossy(output)
+            .lines()
+            .find(|line| line.starts_with(channel))
+            .unwrap_or_else(|| panic!("no output line for {}", channel))
+            .to_string()
+    }
+
+    // GPS channels used to be read through the regular channel accessors, showing
+    // the data of the regular channel with the same index.
+    #[test]
+    fn channels_preview_reads_gps_channels_through_gps_accessors() {
+        let source = session();
+        let mut output = Vec::new();
+
+        super::channels::write_channels_list(&mut output, &source, true, None, OutputFormat::Table)
+            .unwrap();
+
+        let line = output_line(&output, "GPS Speed");
+        assert!(
+            line.trim_end().ends_with("0, 0.5, 1"),
+            "unexpected preview: {}",
+            line
+        );
+        assert!(!line.contains("3000"), "regular channel data in: {}", line);
+    }
+
+    #[test]
+    fn lap_preview_reads_gps_channels_through_gps_accessors() {
+        let source = session();
+        let mut output = Vec::new();
+
+        super::lap::write_run_info(
+            &mut output,
+            &source,
+            &"3".parse::<LapSelection>().unwrap(),
+            OutputFormat::Table,
+        )
+        .unwrap();
+
+        let line = output_line(&output, "3     GPS Speed");
+        assert!(
+            line.trim_end().ends_with("100, 100.5, 101"),
+            "unexpected preview: {}",
+            line
+        );
+        assert!(!line.contains("3200"), "regular channel data in: {}", line);
+    }
+
+    #[test]
+    fn laps_render_as_json() {
+        let source = session();
+        let mut output = Vec::new();
+
+        super::laps::write_laps_info(&mut output, &source, OutputFormat::Json).unwrap();
+
+        let laps: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(laps.as_array().map(|laps| laps.len()), Some(3));
+        assert_eq!(laps[1]["lap"], 2);
+        assert_eq!(laps[1]["start"], 10.0);
+        assert_eq!(laps[1]["lap_time"], "00:10.000");
+    }
+
+    #[test]
+    fn channels_render_as_csv() {
+        let source = session();
+        let mut output = Vec::new();
+
+        super::channels::write_channels_list(&mut output, &source, false, None, OutputFormat::Csv)
+            .unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0], "name,unit,family,count,rate");
+        assert_eq!(lines[2], "GPS Speed,km/h,gps,300,10.0");
+        assert_eq!(lines.len(), 4);
+    }
+
+    #[test]
+    fn info_renders_as_yaml() {
+        let source = session();
+        let mut output = Vec::new();
+
+        super::info::write_run_info(&mut output, &source, OutputFormat::Yaml).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("track: Zandvoort"), "{}", output);
+        assert!(output.contains("gps_raw_channels: 1"), "{}", output);
+    }
+
+    #[test]
+    fn channels_count_only_the_selected_laps() {
+        let source = session();
+        let mut output = Vec::new();
+
+        super::channels::write_channels_list(
+            &mut output,
+            &source,
+            true,
+            Some(&"2-3".parse::<LapSelection>().unwrap()),
+            OutputFormat::Csv,
+        )
+        .unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        let line = output_line(output.as_bytes(), "RPM");
+        assert_eq!(
+            line,
+            "RPM,rpm,regular,200,10.0,\"10, 10.1, 10.2\",\"3100, 3101, 3102\""
+        );
+    }
+}
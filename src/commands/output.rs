@@ -0,0 +1,83 @@
+use crate::error::{Error, Result};
+use serde::Serialize;
+use std::fmt;
+use std::io::Write;
+
+/// How command results are printed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Fixed-width columns for humans.
+    #[default]
+    Table,
+    Json,
+    Yaml,
+    Csv,
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "table" => Ok(OutputFormat::Table),
+            "json" => Ok(OutputFormat::Json),
+            "yaml" => Ok(OutputFormat::Yaml),
+            "csv" => Ok(OutputFormat::Csv),
+            _ => Err(Error::InvalidArgument(format!(
+                "unknown output format '{}' (expected table, json, yaml or csv)",
+                s
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            OutputFormat::Table => "table",
+            OutputFormat::Json => "json",
+            OutputFormat::Yaml => "yaml",
+            OutputFormat::Csv => "csv",
+        };
+        f.write_str(name)
+    }
+}
+
+/// The result of a command, renderable in every output format.
+///
+/// JSON and YAML serialize the report itself, CSV writes one row per record.
+pub trait Report: Serialize {
+    type Record: Serialize;
+
+    fn records(&self) -> Vec<Self::Record>;
+    fn write_table(&self, out: &mut dyn Write) -> Result<()>;
+}
+
+pub fn render<R: Report>(out: &mut dyn Write, format: OutputFormat, report: &R) -> Result<()> {
+    match format {
+        OutputFormat::Table => report.write_table(out)?,
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut *out, report)?;
+            writeln!(out)?;
+        }
+        OutputFormat::Yaml => serde_yaml::to_writer(&mut *out, report)?,
+        OutputFormat::Csv => {
+            let mut writer = ::csv::Writer::from_writer(&mut *out);
+            for record in report.records() {
+                writer.serialize(record)?;
+            }
+            writer.flush()?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Formats the first few values of a preview for a table cell.
+pub(crate) fn join_preview(values: &[f64], format: fn(f64) -> String) -> String {
+    values
+        .iter()
+        .map(|&value| format(value))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
@@ -0,0 +1,81 @@
+//! User configuration, read from `xrk-cli/config.toml` in the user's
+//! configuration directory unless another file is given.
+//!
+//! ```toml
+//! [aliases]
+//! brake_front = "P_BRK_FRONT"
+//! brakes = "re:^P_BRK"
+//!
+//! [units]
+//! "GPS Speed" = "km/h"
+//! ```
+
+use crate::error::{Error, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Names usable in channel selections, mapping to a channel name or pattern.
+    pub aliases: BTreeMap<String, String>,
+    /// Unit to present a channel in, by channel name.
+    pub units: BTreeMap<String, String>,
+}
+
+impl Config {
+    /// `$XDG_CONFIG_HOME/xrk-cli/config.toml`, falling back to `~/.config`.
+    pub fn default_path() -> Option<PathBuf> {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+            .map(|dir| dir.join("xrk-cli").join("config.toml"))
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let config_error = |message: String| Error::Config {
+            path: path.to_path_buf(),
+            message,
+        };
+
+        let text = std::fs::read_to_string(path).map_err(|err| config_error(err.to_string()))?;
+        toml::from_str(&text).map_err(|err| config_error(err.message().to_string()))
+    }
+
+    /// Loads the given file, or the default one if it exists.
+    pub fn load_or_default(path: Option<&Path>) -> Result<Self> {
+        match path {
+            Some(path) => Self::load(path),
+            None => match Self::default_path() {
+                Some(path) if path.exists() => Self::load(&path),
+                _ => Ok(Self::default()),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn aliases_are_read_from_toml() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(file, "[aliases]\nbrake_front = \"P_BRK_FRONT\"").unwrap();
+
+        let config = Config::load(file.path()).unwrap();
+        assert_eq!(
+            config.aliases.get("brake_front").map(String::as_str),
+            Some("P_BRK_FRONT")
+        );
+
+        writeln!(file, "[unknown]").unwrap();
+        assert!(matches!(
+            Config::load(file.path()),
+            Err(Error::Config { .. })
+        ));
+    }
+}
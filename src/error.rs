@@ -0,0 +1,140 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Everything that can go wrong while reading a session or producing output.
+#[derive(Debug)]
+pub enum Error {
+    /// A command line argument has an invalid value.
+    InvalidArgument(String),
+    FileNotFound(PathBuf),
+    /// The file exists but the backend could not load it.
+    Load {
+        path: PathBuf,
+        message: String,
+    },
+    /// A channel name or pattern that matches no channel, with similar
+    /// channel names if there are any.
+    UnknownChannel {
+        name: String,
+        suggestions: Vec<String>,
+    },
+    /// A lap number (starting at 1) that the session does not contain.
+    UnknownLap(usize),
+    /// Output could not be written.
+    Write(io::Error),
+    /// The output file exists and overwriting it was not allowed.
+    OutputExists(PathBuf),
+    /// The configuration file could not be read.
+    Config {
+        path: PathBuf,
+        message: String,
+    },
+}
+
+impl Error {
+    pub fn unknown_channel(name: &str) -> Self {
+        Error::UnknownChannel {
+            name: name.to_string(),
+            suggestions: Vec::new(),
+        }
+    }
+
+    /// Process exit code for this error; 2 is used by clap for usage errors.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::InvalidArgument(_) => 2,
+            Error::FileNotFound(_) => 3,
+            Error::Load { .. } => 4,
+            Error::UnknownChannel { .. } => 5,
+            Error::UnknownLap(_) => 6,
+            Error::Write(_) => 7,
+            Error::OutputExists(_) => 8,
+            Error::Config { .. } => 9,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidArgument(message) => write!(f, "{}", message),
+            Error::FileNotFound(path) => {
+                write!(f, "The file '{}' does not exist.", path.display())
+            }
+            Error::Load { path, message } => {
+                write!(f, "Failed to load '{}': {}", path.display(), message)
+            }
+            Error::UnknownChannel { name, suggestions } => {
+                write!(f, "Unknown channel '{}'", name)?;
+                if !suggestions.is_empty() {
+                    let suggestions: Vec<String> = suggestions
+                        .iter()
+                        .map(|suggestion| format!("'{}'", suggestion))
+                        .collect();
+                    write!(f, ", did you mean {}?", suggestions.join(", "))?;
+                }
+                Ok(())
+            }
+            Error::UnknownLap(number) => write!(f, "Unknown lap {}", number),
+            Error::Write(err) => write!(f, "Failed to write output: {}", err),
+            Error::OutputExists(path) => write!(
+                f,
+                "The file '{}' already exists, use --force to overwrite it.",
+                path.display()
+            ),
+            Error::Config { path, message } => {
+                write!(f, "Invalid configuration '{}': {}", path.display(), message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Write(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Write(err)
+    }
+}
+
+impl From<csv::Error> for Error {
+    fn from(err: csv::Error) -> Self {
+        Error::Write(err.into())
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Error::Write(err.into())
+    }
+}
+
+impl From<serde_yaml::Error> for Error {
+    fn from(err: serde_yaml::Error) -> Self {
+        Error::Write(io::Error::other(err))
+    }
+}
+
+#[cfg(feature = "arrow")]
+impl From<arrow_schema::ArrowError> for Error {
+    fn from(err: arrow_schema::ArrowError) -> Self {
+        Error::Write(io::Error::other(err))
+    }
+}
+
+#[cfg(feature = "parquet")]
+impl From<parquet::errors::ParquetError> for Error {
+    fn from(err: parquet::errors::ParquetError) -> Self {
+        Error::Write(io::Error::other(err))
+    }
+}
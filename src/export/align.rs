@@ -0,0 +1,498 @@
+use super::distance::{DistanceTrace, POSITION_CHANNEL_NAMES, SPEED_CHANNEL_NAME};
+use super::{LapData, MASTER_CHANNEL_NAME};
+use crate::error::{Error, Result};
+use std::fmt;
+
+/// How a channel's value is estimated at a master timestamp.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Interpolation {
+    /// Value of the closest sample.
+    #[default]
+    Nearest,
+    /// Straight line between the surrounding samples.
+    Linear,
+    /// Value of the last sample at or before the timestamp (sample and hold).
+    Previous,
+    /// Cubic Hermite spline through the surrounding samples.
+    Cubic,
+    /// Only samples at the timestamp itself, within the tolerance.
+    None,
+}
+
+impl std::str::FromStr for Interpolation {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "nearest" => Ok(Interpolation::Nearest),
+            "linear" => Ok(Interpolation::Linear),
+            "previous" => Ok(Interpolation::Previous),
+            "cubic" => Ok(Interpolation::Cubic),
+            "none" => Ok(Interpolation::None),
+            _ => Err(Error::InvalidArgument(format!(
+                "unknown interpolation '{}' (expected nearest, linear, previous, cubic or none)",
+                s
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for Interpolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Interpolation::Nearest => "nearest",
+            Interpolation::Linear => "linear",
+            Interpolation::Previous => "previous",
+            Interpolation::Cubic => "cubic",
+            Interpolation::None => "none",
+        };
+        f.write_str(name)
+    }
+}
+
+/// The timestamps channels are aligned onto.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum TimeBase {
+    /// The GPS channel ([`MASTER_CHANNEL_NAME`]) when the lap has it, otherwise
+    /// the channel with the most samples.
+    #[default]
+    Auto,
+    /// The samples of the named channel.
+    Master(String),
+    /// A uniform grid at the given rate in Hz.
+    Rate(f64),
+    /// The times at which every multiple of the given step in metres is
+    /// driven within the lap.
+    Distance(f64),
+}
+
+impl TimeBase {
+    /// Channels the time base is computed from.
+    pub fn channels(&self) -> Vec<&str> {
+        match self {
+            TimeBase::Auto | TimeBase::Rate(_) => Vec::new(),
+            TimeBase::Master(name) => vec![name.as_str()],
+            TimeBase::Distance(_) => {
+                let mut channels = POSITION_CHANNEL_NAMES.to_vec();
+                channels.push(SPEED_CHANNEL_NAME);
+                channels
+            }
+        }
+    }
+}
+
+/// How channels are resampled onto the master timestamps.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AlignOptions {
+    pub time_base: TimeBase,
+    pub interpolation: Interpolation,
+    /// Maximum distance in seconds to the closest channel sample; further away
+    /// the value is missing. Unlimited if not set, except for
+    /// [`Interpolation::None`] which then requires an exact match.
+    pub tolerance: Option<f64>,
+    /// Channels of the laps that are only there to compute the time base;
+    /// they get no column.
+    pub time_base_only: Vec<String>,
+}
+
+impl AlignOptions {
+    /// Whether the channel called `name` gets a column.
+    pub fn is_column(&self, name: &str) -> bool {
+        !self.time_base_only.iter().any(|other| other == name)
+    }
+}
+
+/// A lap with every channel resampled onto a common time base.
+pub struct AlignedLap {
+    pub lap: usize,
+    pub times: Vec<f64>,
+    /// Distance in metres from the start of the lap at each timestamp, when
+    /// aligned on distance.
+    pub distances: Option<Vec<f64>>,
+    /// One column per channel of the lap that [`AlignOptions::is_column`], in
+    /// the same order as `LapData::channels`, with one value per timestamp;
+    /// `None` where the channel has no value.
+    pub columns: Vec<Vec<Option<f64>>>,
+}
+
+/// Aligns channel data to the master channel using nearest-neighbor interpolation.
+pub fn align_nearest(
+    master_times: &[f64],
+    channel_times: &[f64],
+    channel_values: &[f64],
+) -> Vec<Option<f64>> {
+    resample(
+        master_times,
+        channel_times,
+        channel_values,
+        &AlignOptions::default(),
+    )
+}
+
+/// Estimates the channel value at every master timestamp.
+///
+/// Both `master_times` and `channel_times` must be sorted in ascending order,
+/// which lets a single forward pass over the channel find the samples around
+/// every master timestamp. On a tie the earlier sample is the nearest one.
+/// Linear and cubic interpolation do not extrapolate: outside the channel's
+/// time range the first or last sample is used.
+pub fn resample(
+    master_times: &[f64],
+    channel_times: &[f64],
+    channel_values: &[f64],
+    options: &AlignOptions,
+) -> Vec<Option<f64>> {
+    let tolerance = match (options.tolerance, options.interpolation) {
+        (Some(tolerance), _) => tolerance,
+        (None, Interpolation::None) => 0.0,
+        (None, _) => f64::INFINITY,
+    };
+
+    // Index of the first channel sample at or after the master timestamp.
+    let mut next = 0;
+
+    master_times
+        .iter()
+        .map(|&master_time| {
+            while next < channel_times.len() && channel_times[next] < master_time {
+                next += 1;
+            }
+
+            let previous = next.checked_sub(1);
+            let following = (next < channel_times.len()).then_some(next);
+            let distance = |index: usize| (channel_times[index] - master_time).abs();
+
+            let nearest = match (previous, following) {
+                (Some(p), Some(f)) if distance(f) < distance(p) => f,
+                (Some(p), _) => p,
+                (None, Some(f)) => f,
+                (None, None) => return None,
+            };
+
+            // The sample that carries the value, for the tolerance check.
+            let source = match options.interpolation {
+                Interpolation::Previous => match following {
+                    Some(f) if channel_times[f] == master_time => f,
+                    _ => previous?,
+                },
+                _ => nearest,
+            };
+            if distance(source) > tolerance {
+                return None;
+            }
+
+            match (options.interpolation, previous, following) {
+                (Interpolation::Linear, Some(p), Some(f)) if channel_times[f] != master_time => {
+                    Some(linear(channel_times, channel_values, p, f, master_time))
+                }
+                (Interpolation::Cubic, Some(p), Some(f)) if channel_times[f] != master_time => {
+                    Some(cubic(channel_times, channel_values, p, f, master_time))
+                }
+                _ => channel_values.get(source).copied(),
+            }
+        })
+        .collect()
+}
+
+fn linear(times: &[f64], values: &[f64], p: usize, f: usize, time: f64) -> f64 {
+    let ratio = (time - times[p]) / (times[f] - times[p]);
+    values[p] + ratio * (values[f] - values[p])
+}
+
+/// Cubic Hermite interpolation between samples `p` and `f = p + 1`, with the
+/// tangents estimated from the neighbouring samples.
+fn cubic(times: &[f64], values: &[f64], p: usize, f: usize, time: f64) -> f64 {
+    let slope = |from: usize, to: usize| (values[to] - values[from]) / (times[to] - times[from]);
+    let tangent = |index: usize| {
+        let before = index.saturating_sub(1);
+        let after = (index + 1).min(times.len() - 1);
+        slope(before, after)
+    };
+
+    let h = times[f] - times[p];
+    let s = (time - times[p]) / h;
+    let s2 = s * s;
+    let s3 = s2 * s;
+
+    (2.0 * s3 - 3.0 * s2 + 1.0) * values[p]
+        + (s3 - 2.0 * s2 + s) * h * tangent(p)
+        + (-2.0 * s3 + 3.0 * s2) * values[f]
+        + (s3 - s2) * h * tangent(f)
+}
+
+/// Timestamps at multiples of `1 / rate` between the first and last sample of the lap.
+fn uniform_grid(lap: &LapData, rate: f64) -> Vec<f64> {
+    let times = lap
+        .channels
+        .iter()
+        .flat_map(|channel| channel.data.iter().map(|dp| dp.s));
+    let (first, last) = times.fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), t| {
+        (min.min(t), max.max(t))
+    });
+    if first > last {
+        return Vec::new();
+    }
+
+    let from = (first * rate).ceil() as i64;
+    let to = (last * rate).floor() as i64;
+    (from..=to).map(|step| step as f64 / rate).collect()
+}
+
+/// Aligns all channels of a lap onto the time base of `options`.
+///
+/// Fails if the requested master channel is not part of the lap.
+pub fn align_lap(lap: &LapData, options: &AlignOptions) -> Result<AlignedLap> {
+    let master = match &options.time_base {
+        TimeBase::Auto => lap
+            .channels
+            .iter()
+            .position(|channel| channel.name == MASTER_CHANNEL_NAME)
+            .or_else(|| (0..lap.channels.len()).max_by_key(|&i| lap.channels[i].data.len())),
+        TimeBase::Master(name) => Some(
+            lap.channels
+                .iter()
+                .position(|channel| &channel.name == name)
+                .ok_or_else(|| Error::unknown_channel(name))?,
+        ),
+        TimeBase::Rate(rate) if !(rate.is_finite() && *rate > 0.0) => {
+            return Err(Error::InvalidArgument(format!(
+                "invalid resampling rate {}",
+                rate
+            )));
+        }
+        TimeBase::Distance(step) if !(step.is_finite() && *step > 0.0) => {
+            return Err(Error::InvalidArgument(format!(
+                "invalid distance step {}",
+                step
+            )));
+        }
+        TimeBase::Rate(_) | TimeBase::Distance(_) => None,
+    };
+
+    let mut distances = None;
+    let times: Vec<f64> = match (master, &options.time_base) {
+        (Some(index), _) => lap.channels[index].data.iter().map(|dp| dp.s).collect(),
+        (None, TimeBase::Rate(rate)) => uniform_grid(lap, *rate),
+        (None, TimeBase::Distance(step)) => {
+            let trace = DistanceTrace::from_lap(lap).ok_or_else(|| {
+                Error::InvalidArgument(format!(
+                    "lap {} has no ECEF position or {} channels to compute the distance from",
+                    lap.lap + 1,
+                    SPEED_CHANNEL_NAME
+                ))
+            })?;
+            let (times, lap_distances) = trace.times_at_step(*step);
+            distances = Some(lap_distances);
+            times
+        }
+        (None, _) => Vec::new(),
+    };
+
+    let mut columns: Vec<Vec<Option<f64>>> = Vec::with_capacity(lap.channels.len());
+
+    for (i, channel) in lap.channels.iter().enumerate() {
+        if !options.is_column(&channel.name) {
+            continue;
+        }
+        if master == Some(i) {
+            // Add the master channel directly
+            columns.push(channel.data.iter().map(|dp| Some(dp.v)).collect());
+            continue;
+        }
+
+        let channel_times: Vec<f64> = channel.data.iter().map(|dp| dp.s).collect();
+        let channel_values: Vec<f64> = channel.data.iter().map(|dp| dp.v).collect();
+        columns.push(resample(&times, &channel_times, &channel_values, options));
+    }
+
+    Ok(AlignedLap {
+        lap: lap.lap,
+        times,
+        distances,
+        columns,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::{ChannelData, DataPoint};
+
+    fn align_nearest_by_scan(
+        master_times: &[f64],
+        channel_times: &[f64],
+        channel_values: &[f64],
+    ) -> Vec<Option<f64>> {
+        master_times
+            .iter()
+            .map(|&master_time| {
+                channel_times
+                    .iter()
+                    .zip(channel_values.iter())
+                    .min_by(|(time1, _), (time2, _)| {
+                        (*time1 - master_time)
+                            .abs()
+                            .partial_cmp(&(*time2 - master_time).abs())
+                            .unwrap()
+                    })
+                    .map(|(_, &value)| value)
+            })
+            .collect()
+    }
+
+    fn options(interpolation: Interpolation, tolerance: Option<f64>) -> AlignOptions {
+        AlignOptions {
+            interpolation,
+            tolerance,
+            ..AlignOptions::default()
+        }
+    }
+
+    fn lap(channels: &[(&str, &[f64])]) -> LapData {
+        LapData {
+            lap: 0,
+            channels: channels
+                .iter()
+                .map(|(name, times)| ChannelData {
+                    name: name.to_string(),
+                    unit: String::new(),
+                    data: times.iter().map(|&s| DataPoint { s, v: s * 2.0 }).collect(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn nearest_matches_a_full_scan() {
+        let master_times: Vec<f64> = (0..200).map(|i| i as f64 * 0.05 - 1.0).collect();
+        let channel_times: Vec<f64> = (0..700).map(|i| (i as f64 * 0.013).powf(1.1)).collect();
+        let channel_values: Vec<f64> = (0..700).map(|i| i as f64).collect();
+
+        assert_eq!(
+            align_nearest(&master_times, &channel_times, &channel_values),
+            align_nearest_by_scan(&master_times, &channel_times, &channel_values)
+        );
+    }
+
+    #[test]
+    fn nearest_prefers_the_earlier_sample_on_a_tie() {
+        assert_eq!(
+            align_nearest(&[0.5, 1.5], &[0.0, 1.0, 2.0], &[10.0, 11.0, 12.0]),
+            vec![Some(10.0), Some(11.0)]
+        );
+        assert_eq!(align_nearest(&[0.5], &[], &[]), vec![None]);
+    }
+
+    #[test]
+    fn interpolation_modes() {
+        let master = [-1.0, 0.0, 0.25, 1.0, 1.5, 3.0];
+        let times = [0.0, 1.0, 2.0];
+        let values = [0.0, 10.0, 40.0];
+        let resampled =
+            |interpolation| resample(&master, &times, &values, &options(interpolation, None));
+
+        assert_eq!(
+            resampled(Interpolation::Linear),
+            vec![
+                Some(0.0),
+                Some(0.0),
+                Some(2.5),
+                Some(10.0),
+                Some(25.0),
+                Some(40.0)
+            ]
+        );
+        assert_eq!(
+            resampled(Interpolation::Previous),
+            vec![
+                None,
+                Some(0.0),
+                Some(0.0),
+                Some(10.0),
+                Some(10.0),
+                Some(40.0)
+            ]
+        );
+        assert_eq!(
+            resampled(Interpolation::None),
+            vec![None, Some(0.0), None, Some(10.0), None, None]
+        );
+
+        // The spline passes through the samples and bends towards the steeper side.
+        let cubic = resampled(Interpolation::Cubic);
+        assert_eq!(cubic[1], Some(0.0));
+        assert_eq!(cubic[3], Some(10.0));
+        assert!(cubic[4].unwrap() < 25.0);
+    }
+
+    #[test]
+    fn values_outside_the_tolerance_are_missing() {
+        // A logging gap between 1 and 5 seconds.
+        let master = [0.0, 1.0, 2.0, 3.0, 4.0, 5.0];
+        let times = [0.0, 1.0, 5.0];
+        let values = [1.0, 2.0, 3.0];
+
+        for interpolation in [
+            Interpolation::Nearest,
+            Interpolation::Linear,
+            Interpolation::Cubic,
+        ] {
+            let resampled = resample(&master, &times, &values, &options(interpolation, Some(0.5)));
+            assert_eq!(resampled[2..5], [None, None, None], "{}", interpolation);
+            assert_eq!(resampled[5], Some(3.0), "{}", interpolation);
+        }
+
+        assert_eq!(
+            resample(
+                &master,
+                &times,
+                &values,
+                &options(Interpolation::Previous, Some(1.5))
+            ),
+            vec![Some(1.0), Some(2.0), Some(2.0), None, None, Some(3.0)]
+        );
+    }
+
+    #[test]
+    fn time_base_defaults_to_gps_then_the_densest_channel() {
+        let with_gps = lap(&[
+            ("RPM", &[0.0, 0.5, 1.0]),
+            (MASTER_CHANNEL_NAME, &[0.0, 1.0]),
+        ]);
+        let aligned = align_lap(&with_gps, &AlignOptions::default()).unwrap();
+        assert_eq!(aligned.times, vec![0.0, 1.0]);
+        assert_eq!(aligned.columns[0], vec![Some(0.0), Some(2.0)]);
+
+        let without_gps = lap(&[("Gear", &[0.0, 1.0]), ("RPM", &[0.0, 0.5, 1.0])]);
+        let aligned = align_lap(&without_gps, &AlignOptions::default()).unwrap();
+        assert_eq!(aligned.times, vec![0.0, 0.5, 1.0]);
+    }
+
+    #[test]
+    fn time_base_can_be_a_channel_or_a_uniform_grid() {
+        let lap = lap(&[("RPM", &[0.05, 0.5, 1.0]), ("Gear", &[0.2, 1.32])]);
+
+        let master = AlignOptions {
+            time_base: TimeBase::Master("Gear".to_string()),
+            ..AlignOptions::default()
+        };
+        assert_eq!(align_lap(&lap, &master).unwrap().times, vec![0.2, 1.32]);
+
+        let rate = AlignOptions {
+            time_base: TimeBase::Rate(4.0),
+            ..AlignOptions::default()
+        };
+        let aligned = align_lap(&lap, &rate).unwrap();
+        assert_eq!(aligned.times, vec![0.25, 0.5, 0.75, 1.0, 1.25]);
+        assert_eq!(aligned.columns[1][0], Some(0.4));
+
+        let unknown = AlignOptions {
+            time_base: TimeBase::Master("Speed".to_string()),
+            ..AlignOptions::default()
+        };
+        assert!(matches!(
+            align_lap(&lap, &unknown),
+            Err(Error::UnknownChannel { .. })
+        ));
+    }
+}
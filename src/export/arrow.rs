@@ -0,0 +1,123 @@
+use super::align::AlignOptions;
+use super::{columnar, LapData};
+use crate::error::Result;
+use arrow_ipc::writer::{FileWriter, StreamWriter};
+use std::collections::HashMap;
+use std::io;
+use std::sync::Arc;
+
+/// Flavour of Arrow IPC to write.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IpcFormat {
+    /// The random access file format, also known as Feather v2.
+    #[default]
+    File,
+    /// The streaming format, which readers can consume as it is written.
+    Stream,
+}
+
+/// Writes the laps as Arrow IPC with the channels aligned as for CSV, one
+/// record batch per lap. Units are kept in the field metadata and `metadata`
+/// (see [`columnar::session_metadata`]) in the schema metadata.
+pub fn write_arrow<W: io::Write>(
+    laps: &[LapData],
+    options: &AlignOptions,
+    metadata: HashMap<String, String>,
+    format: IpcFormat,
+    writer: W,
+) -> Result<()> {
+    let schema = Arc::new(columnar::schema(laps, options).with_metadata(metadata));
+
+    match format {
+        IpcFormat::File => {
+            let mut writer = FileWriter::try_new(writer, &schema)?;
+            for lap in laps {
+                writer.write(&columnar::lap_batch(&schema, lap, options)?)?;
+            }
+            writer.finish()?;
+        }
+        IpcFormat::Stream => {
+            let mut writer = StreamWriter::try_new(writer, &schema)?;
+            for lap in laps {
+                writer.write(&columnar::lap_batch(&schema, lap, options)?)?;
+                // Hand every lap to the reader as soon as it is ready.
+                writer.flush()?;
+            }
+            writer.finish()?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::ChannelCatalog;
+    use crate::source::{ChannelFamily, MemorySource};
+    use arrow_ipc::reader::{FileReader, StreamReader};
+
+    fn session() -> MemorySource {
+        let timestamps: Vec<f64> = (0..20).map(|i| i as f64).collect();
+
+        MemorySource::new()
+            .with_track("Zandvoort")
+            .with_lap(0.0, 10.0)
+            .with_lap(10.0, 10.0)
+            .with_channel(
+                ChannelFamily::Regular,
+                "Oil Temp",
+                "°C",
+                timestamps.clone(),
+                timestamps.clone(),
+            )
+    }
+
+    fn write(format: IpcFormat) -> Vec<u8> {
+        let source = session();
+        let catalog = ChannelCatalog::build(&source);
+        let entries: Vec<_> = catalog.iter().collect();
+        let data = crate::export::collect(&source, &entries, &[0, 1]);
+        let mut output = Vec::new();
+
+        write_arrow(
+            &data.laps,
+            &AlignOptions::default(),
+            columnar::session_metadata(&source, &[0, 1]),
+            format,
+            &mut output,
+        )
+        .unwrap();
+
+        output
+    }
+
+    #[test]
+    fn file_has_a_batch_per_lap_and_the_session_in_the_schema() {
+        let reader = FileReader::try_new(io::Cursor::new(write(IpcFormat::File)), None).unwrap();
+
+        let schema = reader.schema();
+        assert_eq!(
+            schema.metadata().get("track").map(String::as_str),
+            Some("Zandvoort")
+        );
+        let laps: serde_json::Value = serde_json::from_str(&schema.metadata()["laps"]).unwrap();
+        assert_eq!(laps[1]["lap"], 2);
+        assert_eq!(laps[1]["start"], 10.0);
+        assert_eq!(
+            schema.field_with_name("Oil Temp").unwrap().metadata()[columnar::UNIT_METADATA_KEY],
+            "°C"
+        );
+
+        assert_eq!(reader.num_batches(), 2);
+    }
+
+    #[test]
+    fn stream_can_be_read_back() {
+        let reader =
+            StreamReader::try_new(io::Cursor::new(write(IpcFormat::Stream)), None).unwrap();
+
+        let rows: Vec<usize> = reader.map(|batch| batch.unwrap().num_rows()).collect();
+        assert_eq!(rows, vec![10, 10]);
+    }
+}
@@ -0,0 +1,101 @@
+//! Aligned laps as Arrow record batches, shared by the columnar exports.
+
+use super::align::{align_lap, AlignOptions, TimeBase};
+use super::LapData;
+use crate::error::Result;
+use crate::source::TelemetrySource;
+use arrow_array::{ArrayRef, Float64Array, RecordBatch, UInt32Array};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Field metadata key holding the unit of a column.
+pub const UNIT_METADATA_KEY: &str = "unit";
+
+fn field(name: &str, data_type: DataType, nullable: bool, unit: &str) -> Field {
+    Field::new(name, data_type, nullable).with_metadata(HashMap::from([(
+        UNIT_METADATA_KEY.to_string(),
+        unit.to_string(),
+    )]))
+}
+
+/// Lap number, time, distance when aligned on distance, and one nullable
+/// float64 column per channel of the first lap that gets a column.
+pub fn schema(laps: &[LapData], options: &AlignOptions) -> Schema {
+    let mut fields = vec![
+        Field::new("lap", DataType::UInt32, false),
+        field("time", DataType::Float64, false, "s"),
+    ];
+    if let TimeBase::Distance(_) = options.time_base {
+        fields.push(field("distance", DataType::Float64, false, "m"));
+    }
+    if let Some(first_lap) = laps.first() {
+        for channel in first_lap
+            .channels
+            .iter()
+            .filter(|channel| options.is_column(&channel.name))
+        {
+            fields.push(field(&channel.name, DataType::Float64, true, &channel.unit));
+        }
+    }
+
+    Schema::new(fields)
+}
+
+/// Session details and the start and duration of the exported laps, as schema
+/// metadata. The laps are a JSON array under `laps`.
+pub fn session_metadata(source: &dyn TelemetrySource, laps: &[usize]) -> HashMap<String, String> {
+    let mut metadata: HashMap<String, String> = [
+        ("championship", source.championship()),
+        ("track", source.track()),
+        ("venue_type", source.venue_type()),
+        ("vehicle", source.vehicle()),
+        ("racer", source.racer()),
+        (
+            "datetime",
+            source.datetime().map(|datetime| datetime.to_string()),
+        ),
+    ]
+    .into_iter()
+    .filter_map(|(key, value)| Some((key.to_string(), value?)))
+    .collect();
+
+    let laps: Vec<serde_json::Value> = laps
+        .iter()
+        .filter_map(|&lap| source.lap_info(lap))
+        .map(|info| {
+            serde_json::json!({
+                "lap": info.number(),
+                "start": info.start(),
+                "time": info.time(),
+            })
+        })
+        .collect();
+    metadata.insert(
+        "laps".to_string(),
+        serde_json::Value::from(laps).to_string(),
+    );
+
+    metadata
+}
+
+/// The aligned rows of a lap.
+pub fn lap_batch(schema: &SchemaRef, lap: &LapData, options: &AlignOptions) -> Result<RecordBatch> {
+    let aligned = align_lap(lap, options)?;
+
+    let mut columns: Vec<ArrayRef> = vec![
+        Arc::new(UInt32Array::from(vec![
+            (lap.lap + 1) as u32;
+            aligned.times.len()
+        ])),
+        Arc::new(Float64Array::from(aligned.times)),
+    ];
+    if let Some(distances) = aligned.distances {
+        columns.push(Arc::new(Float64Array::from(distances)));
+    }
+    for column in aligned.columns {
+        columns.push(Arc::new(Float64Array::from(column)));
+    }
+
+    Ok(RecordBatch::try_new(schema.clone(), columns)?)
+}
@@ -0,0 +1,56 @@
+use super::align::{align_lap, AlignOptions, TimeBase};
+use super::LapData;
+use crate::error::Result;
+use std::io;
+
+/// Writes the laps as CSV, one row per timestamp of the time base.
+pub fn write_csv<W: io::Write>(laps: &[LapData], options: &AlignOptions, writer: W) -> Result<()> {
+    let mut writer = ::csv::Writer::from_writer(writer);
+
+    // Create the CSV header
+    let mut headers = vec!["lap (#)".to_string(), "time (s)".to_string()];
+    if let TimeBase::Distance(_) = options.time_base {
+        headers.push("distance (m)".to_string());
+    }
+    if let Some(first_lap) = laps.first() {
+        for channel in first_lap
+            .channels
+            .iter()
+            .filter(|channel| options.is_column(&channel.name))
+        {
+            let header_value = channel.name.clone() + " (" + &channel.unit + ")";
+            headers.push(header_value);
+        }
+    }
+    writer.write_record(&headers)?;
+
+    // Write data rows aligned to the time base
+    for lap in laps {
+        let aligned = align_lap(lap, options)?;
+
+        for (i, &master_time) in aligned.times.iter().enumerate() {
+            let mut row = vec![(lap.lap + 1).to_string(), format!("{:.3}", master_time)];
+            if let Some(distances) = &aligned.distances {
+                row.push(distances[i].to_string());
+            }
+
+            for aligned_channel in &aligned.columns {
+                match aligned_channel[i] {
+                    Some(value) => row.push(value.to_string()),
+                    None => row.push(String::new()), // Missing channel or value
+                }
+            }
+
+            writer.write_record(&row)?;
+        }
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// Exports the laps and channel data to a CSV file.
+pub fn export_to_csv(laps: &[LapData], options: &AlignOptions, file_path: &str) -> Result<()> {
+    let file = std::fs::File::create(file_path)?;
+    write_csv(laps, options, file)
+}
@@ -0,0 +1,204 @@
+//! Distance travelled within a lap, from the GPS channels.
+
+use super::{ChannelData, LapData};
+use crate::geo::is_fix;
+use crate::units::Conversion;
+
+/// ECEF position channels.
+pub const POSITION_CHANNEL_NAMES: [&str; 3] =
+    ["ECEF position_X", "ECEF position_Y", "ECEF position_Z"];
+
+/// Ground speed channel, integrated when the positions are missing.
+pub const SPEED_CHANNEL_NAME: &str = "GPS Speed";
+
+/// Cumulative distance in metres at each timestamp of a lap, starting at 0.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DistanceTrace {
+    pub times: Vec<f64>,
+    pub distances: Vec<f64>,
+}
+
+impl DistanceTrace {
+    /// Computes the trace from the ECEF positions if the lap has all three,
+    /// otherwise from the GPS speed.
+    pub fn from_lap(lap: &LapData) -> Option<Self> {
+        let channel = |name: &str| lap.channels.iter().find(|channel| channel.name == name);
+
+        match POSITION_CHANNEL_NAMES.map(channel) {
+            [Some(x), Some(y), Some(z)] => Self::from_positions(x, y, z),
+            _ => channel(SPEED_CHANNEL_NAME).and_then(Self::from_speed),
+        }
+    }
+
+    /// Positions recorded before the GPS module had a fix are left out.
+    fn from_positions(x: &ChannelData, y: &ChannelData, z: &ChannelData) -> Option<Self> {
+        let to_metres = Conversion::between(&x.unit, "m")?.apply(1.0);
+        let count = x.data.len().min(y.data.len()).min(z.data.len());
+        let mut times = Vec::with_capacity(count);
+        let mut distances = Vec::with_capacity(count);
+        let mut distance = 0.0;
+        let mut previous: Option<[f64; 3]> = None;
+
+        for i in 0..count {
+            let position = [x.data[i].v, y.data[i].v, z.data[i].v];
+            if !is_fix(position) {
+                continue;
+            }
+            if let Some(previous) = previous {
+                let [dx, dy, dz] = [0, 1, 2].map(|axis| position[axis] - previous[axis]);
+                distance += (dx * dx + dy * dy + dz * dz).sqrt() * to_metres;
+            }
+            previous = Some(position);
+            times.push(x.data[i].s);
+            distances.push(distance);
+        }
+
+        Some(Self { times, distances })
+    }
+
+    fn from_speed(speed: &ChannelData) -> Option<Self> {
+        let to_metres_per_second = Conversion::between(&speed.unit, "m/s")?;
+
+        let mut distance = 0.0;
+        let distances = speed
+            .data
+            .iter()
+            .enumerate()
+            .map(|(i, point)| {
+                if i > 0 {
+                    let previous = &speed.data[i - 1];
+                    let average = to_metres_per_second.apply((previous.v + point.v) / 2.0);
+                    distance += average * (point.s - previous.s);
+                }
+                distance
+            })
+            .collect();
+
+        Some(Self {
+            times: speed.data.iter().map(|point| point.s).collect(),
+            distances,
+        })
+    }
+
+    pub fn total(&self) -> f64 {
+        self.distances.last().copied().unwrap_or(0.0)
+    }
+
+    /// Times at which the distances `0, step, 2 * step, ...` are reached,
+    /// interpolated linearly between the samples of the trace.
+    pub fn times_at_step(&self, step: f64) -> (Vec<f64>, Vec<f64>) {
+        if self.times.is_empty() {
+            return (Vec::new(), Vec::new());
+        }
+
+        let steps = (self.total() / step).floor() as usize;
+        let mut sample = 0;
+
+        (0..=steps)
+            .map(|i| {
+                let distance = i as f64 * step;
+                while sample + 1 < self.distances.len() && self.distances[sample + 1] < distance {
+                    sample += 1;
+                }
+
+                let time = match (self.distances.get(sample), self.distances.get(sample + 1)) {
+                    (Some(&from), Some(&to)) if to > from && distance > from => {
+                        let ratio = ((distance - from) / (to - from)).min(1.0);
+                        self.times[sample] + ratio * (self.times[sample + 1] - self.times[sample])
+                    }
+                    _ => self.times[sample],
+                };
+                (time, distance)
+            })
+            .unzip()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::DataPoint;
+
+    fn channel(name: &str, unit: &str, points: &[(f64, f64)]) -> ChannelData {
+        ChannelData {
+            name: name.to_string(),
+            unit: unit.to_string(),
+            data: points.iter().map(|&(s, v)| DataPoint { s, v }).collect(),
+        }
+    }
+
+    #[test]
+    fn distance_from_positions() {
+        let lap = LapData {
+            lap: 0,
+            channels: vec![
+                channel(
+                    "ECEF position_X",
+                    "m",
+                    &[(0.0, 10.0), (1.0, 13.0), (2.0, 13.0)],
+                ),
+                channel(
+                    "ECEF position_Y",
+                    "m",
+                    &[(0.0, 20.0), (1.0, 24.0), (2.0, 24.0)],
+                ),
+                channel(
+                    "ECEF position_Z",
+                    "m",
+                    &[(0.0, 30.0), (1.0, 30.0), (2.0, 32.0)],
+                ),
+            ],
+        };
+
+        let trace = DistanceTrace::from_lap(&lap).unwrap();
+        assert_eq!(trace.distances, vec![0.0, 5.0, 7.0]);
+
+        let (times, distances) = trace.times_at_step(2.0);
+        assert_eq!(distances, vec![0.0, 2.0, 4.0, 6.0]);
+        assert_eq!(times, vec![0.0, 0.4, 0.8, 1.5]);
+    }
+
+    #[test]
+    fn distance_starts_at_the_first_fix() {
+        let lap = LapData {
+            lap: 0,
+            channels: vec![
+                channel(
+                    "ECEF position_X",
+                    "m",
+                    &[(0.0, 0.0), (0.5, 3_900_000.0), (1.0, 3_900_003.0)],
+                ),
+                channel(
+                    "ECEF position_Y",
+                    "m",
+                    &[(0.0, 0.0), (0.5, 300_000.0), (1.0, 300_004.0)],
+                ),
+                channel(
+                    "ECEF position_Z",
+                    "m",
+                    &[(0.0, 0.0), (0.5, 5_000_000.0), (1.0, 5_000_000.0)],
+                ),
+            ],
+        };
+
+        let trace = DistanceTrace::from_lap(&lap).unwrap();
+        assert_eq!(trace.times, vec![0.5, 1.0]);
+        assert_eq!(trace.distances, vec![0.0, 5.0]);
+        assert_eq!(trace.times_at_step(1.0).0.len(), 6);
+    }
+
+    #[test]
+    fn distance_from_speed() {
+        let lap = LapData {
+            lap: 0,
+            channels: vec![channel(
+                "GPS Speed",
+                "km/h",
+                &[(0.0, 36.0), (1.0, 36.0), (2.0, 72.0)],
+            )],
+        };
+
+        let trace = DistanceTrace::from_lap(&lap).unwrap();
+        assert_eq!(trace.distances, vec![0.0, 10.0, 25.0]);
+    }
+}
@@ -0,0 +1,160 @@
+use super::track::track_points;
+use super::LapData;
+use crate::error::Result;
+use chrono::{Duration, NaiveDateTime};
+use std::io;
+
+/// Escapes text for XML content and attribute values.
+pub(super) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Writes the laps as a GPX track with a segment per lap, from the ECEF
+/// positions and the GPS speed. Laps without positions get an empty segment.
+///
+/// Point times are `start` (the session date and time) plus the sample time.
+/// The logger records local time without a time zone, so they are written
+/// without one too.
+pub fn write_gpx<W: io::Write>(
+    laps: &[LapData],
+    name: Option<&str>,
+    start: Option<NaiveDateTime>,
+    mut writer: W,
+) -> Result<()> {
+    let time = |seconds: f64| {
+        start.map(|start| {
+            (start + Duration::microseconds((seconds * 1e6).round() as i64))
+                .format("%Y-%m-%dT%H:%M:%S%.3f")
+                .to_string()
+        })
+    };
+
+    writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        writer,
+        r#"<gpx version="1.1" creator="xrk-cli" xmlns="http://www.topografix.com/GPX/1/1" xmlns:gpxtpx="http://www.garmin.com/xmlschemas/TrackPointExtension/v2">"#
+    )?;
+    writeln!(writer, " <metadata>")?;
+    if let Some(name) = name {
+        writeln!(writer, "  <name>{}</name>", escape(name))?;
+    }
+    if let Some(time) = time(0.0) {
+        writeln!(writer, "  <time>{}</time>", time)?;
+    }
+    writeln!(writer, " </metadata>")?;
+    writeln!(writer, " <trk>")?;
+    if let Some(name) = name {
+        writeln!(writer, "  <name>{}</name>", escape(name))?;
+    }
+
+    for lap in laps {
+        writeln!(writer, "  <trkseg>")?;
+        for point in track_points(lap).unwrap_or_default() {
+            write!(
+                writer,
+                r#"   <trkpt lat="{:.8}" lon="{:.8}"><ele>{:.2}</ele>"#,
+                point.position.latitude, point.position.longitude, point.position.altitude
+            )?;
+            if let Some(time) = time(point.time) {
+                write!(writer, "<time>{}</time>", time)?;
+            }
+            if let Some(speed) = point.speed {
+                write!(
+                    writer,
+                    "<extensions><gpxtpx:TrackPointExtension><gpxtpx:speed>{:.3}</gpxtpx:speed></gpxtpx:TrackPointExtension></extensions>",
+                    speed
+                )?;
+            }
+            writeln!(writer, "</trkpt>")?;
+        }
+        writeln!(writer, "  </trkseg>")?;
+    }
+
+    writeln!(writer, " </trk>")?;
+    writeln!(writer, "</gpx>")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::{ChannelData, DataPoint};
+    use crate::geo::Geodetic;
+    use chrono::NaiveDate;
+
+    fn lap(lap: usize, start: f64) -> LapData {
+        let [x, y, z] = Geodetic {
+            latitude: 52.3888,
+            longitude: 4.5409,
+            altitude: 12.0,
+        }
+        .to_ecef();
+        let channel = |name: &str, unit: &str, value: f64| ChannelData {
+            name: name.to_string(),
+            unit: unit.to_string(),
+            data: vec![
+                DataPoint { s: start, v: value },
+                DataPoint {
+                    s: start + 0.1,
+                    v: value,
+                },
+            ],
+        };
+
+        LapData {
+            lap,
+            channels: vec![
+                channel("ECEF position_X", "m", x),
+                channel("ECEF position_Y", "m", y),
+                channel("ECEF position_Z", "m", z),
+                channel("GPS Speed", "km/h", 36.0),
+            ],
+        }
+    }
+
+    #[test]
+    fn laps_become_segments_of_geodetic_points() {
+        let start = NaiveDate::from_ymd_opt(2024, 5, 1)
+            .unwrap()
+            .and_hms_opt(14, 30, 0)
+            .unwrap();
+        let mut output = Vec::new();
+
+        write_gpx(
+            &[lap(0, 0.0), lap(1, 90.5)],
+            Some("Zandvoort & co"),
+            Some(start),
+            &mut output,
+        )
+        .unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output.matches("<trkseg>").count(), 2);
+        assert_eq!(output.matches("<trkpt ").count(), 4);
+        assert!(output.contains("<name>Zandvoort &amp; co</name>"));
+        assert!(output.contains(
+            r#"<trkpt lat="52.38880000" lon="4.54090000"><ele>12.00</ele><time>2024-05-01T14:31:30.600</time>"#
+        ));
+        assert!(output.contains("<gpxtpx:speed>10.000</gpxtpx:speed>"));
+    }
+
+    #[test]
+    fn positions_before_the_first_fix_are_left_out() {
+        let mut lap = lap(0, 0.5);
+        for channel in &mut lap.channels[..3] {
+            channel.data.insert(0, DataPoint { s: 0.0, v: 0.0 });
+        }
+        let mut output = Vec::new();
+
+        write_gpx(&[lap], None, None, &mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output.matches("<trkpt ").count(), 2);
+        assert!(!output.contains(r#"lat="0.00000000""#), "{}", output);
+    }
+}
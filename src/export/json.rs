@@ -0,0 +1,143 @@
+use super::{ExportData, LapData};
+use crate::error::{Error, Result};
+use serde::Serialize;
+use std::io::{self, Write};
+
+/// What a single NDJSON line holds.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NdjsonRecord {
+    /// All samples of a channel within a lap.
+    #[default]
+    Channel,
+    /// A single sample.
+    Sample,
+}
+
+impl std::str::FromStr for NdjsonRecord {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "channel" => Ok(NdjsonRecord::Channel),
+            "sample" => Ok(NdjsonRecord::Sample),
+            _ => Err(Error::InvalidArgument(format!(
+                "unknown NDJSON record '{}' (expected channel or sample)",
+                s
+            ))),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ChannelLine<'a> {
+    /// Lap number, starting at 1.
+    lap: usize,
+    #[serde(flatten)]
+    channel: &'a super::ChannelData,
+}
+
+#[derive(Serialize)]
+struct SampleLine<'a> {
+    /// Lap number, starting at 1.
+    lap: usize,
+    name: &'a str,
+    unit: &'a str,
+    s: f64,
+    v: f64,
+}
+
+/// Writes the export data as a single JSON document.
+pub fn write_json<W: Write>(data: &ExportData, mut writer: W) -> Result<()> {
+    serde_json::to_writer(&mut writer, data)?;
+    writeln!(writer)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes the laps as newline-delimited JSON, one object per channel or per sample.
+pub fn write_ndjson<W: Write>(laps: &[LapData], record: NdjsonRecord, writer: W) -> Result<()> {
+    let mut writer = io::BufWriter::new(writer);
+
+    for lap in laps {
+        for channel in &lap.channels {
+            match record {
+                NdjsonRecord::Channel => {
+                    let line = ChannelLine {
+                        lap: lap.lap + 1,
+                        channel,
+                    };
+                    serde_json::to_writer(&mut writer, &line)?;
+                    writeln!(writer)?;
+                }
+                NdjsonRecord::Sample => {
+                    for point in &channel.data {
+                        let line = SampleLine {
+                            lap: lap.lap + 1,
+                            name: &channel.name,
+                            unit: &channel.unit,
+                            s: point.s,
+                            v: point.v,
+                        };
+                        serde_json::to_writer(&mut writer, &line)?;
+                        writeln!(writer)?;
+                    }
+                }
+            }
+        }
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::{ChannelData, DataPoint};
+
+    fn laps() -> Vec<LapData> {
+        vec![LapData {
+            lap: 0,
+            channels: vec![ChannelData {
+                name: "RPM".to_string(),
+                unit: "rpm".to_string(),
+                data: vec![
+                    DataPoint { s: 0.0, v: 1000.0 },
+                    DataPoint { s: 0.5, v: 1100.0 },
+                ],
+            }],
+        }]
+    }
+
+    #[test]
+    fn ndjson_writes_one_line_per_channel() {
+        let mut output = Vec::new();
+        write_ndjson(&laps(), NdjsonRecord::Channel, &mut output).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "{\"lap\":1,\"name\":\"RPM\",\"unit\":\"rpm\",\"data\":[{\"s\":0.0,\"v\":1000.0},{\"s\":0.5,\"v\":1100.0}]}\n"
+        );
+    }
+
+    #[test]
+    fn json_has_lap_numbers() {
+        let mut output = Vec::new();
+        write_json(&ExportData { laps: laps() }, &mut output).unwrap();
+
+        let json: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(json["laps"][0]["lap"], 1);
+    }
+
+    #[test]
+    fn ndjson_writes_one_line_per_sample() {
+        let mut output = Vec::new();
+        write_ndjson(&laps(), NdjsonRecord::Sample, &mut output).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "{\"lap\":1,\"name\":\"RPM\",\"unit\":\"rpm\",\"s\":0.0,\"v\":1000.0}\n\
+             {\"lap\":1,\"name\":\"RPM\",\"unit\":\"rpm\",\"s\":0.5,\"v\":1100.0}\n"
+        );
+    }
+}
@@ -0,0 +1,308 @@
+//! Map exports of the laps as lines, with channel values at every point to
+//! colour them by.
+
+use super::align::AlignOptions;
+use super::gpx::escape;
+use super::track::{track_points, values_at_points, TrackPoint};
+use super::{ChannelData, LapData};
+use crate::error::Result;
+use chrono::{Duration, NaiveDateTime};
+use serde_json::{json, Map, Value};
+use std::io;
+
+/// A lap with its track points and the values of the property channels at
+/// each of them.
+struct MapLap<'a> {
+    number: usize,
+    points: Vec<TrackPoint>,
+    properties: Vec<(&'a ChannelData, Vec<Option<f64>>)>,
+}
+
+fn map_laps<'a>(
+    laps: &'a [LapData],
+    properties: &[String],
+    options: &AlignOptions,
+) -> Vec<MapLap<'a>> {
+    laps.iter()
+        .map(|lap| {
+            let points = track_points(lap).unwrap_or_default();
+            let properties = properties
+                .iter()
+                .filter_map(|name| lap.channels.iter().find(|channel| &channel.name == name))
+                .map(|channel| (channel, values_at_points(&points, channel, options)))
+                .collect();
+
+            MapLap {
+                number: lap.lap + 1,
+                points,
+                properties,
+            }
+        })
+        .collect()
+}
+
+/// Writes the laps as a GeoJSON feature collection with a LineString per lap.
+///
+/// The values of the `properties` channels and the time at every point are
+/// arrays in the `coordinateProperties` of the lap, the units in `units`.
+pub fn write_geojson<W: io::Write>(
+    laps: &[LapData],
+    properties: &[String],
+    options: &AlignOptions,
+    writer: W,
+) -> Result<()> {
+    let features: Vec<Value> = map_laps(laps, properties, options)
+        .into_iter()
+        .map(|lap| {
+            let coordinates: Vec<Value> = lap
+                .points
+                .iter()
+                .map(|point| {
+                    json!([
+                        point.position.longitude,
+                        point.position.latitude,
+                        point.position.altitude
+                    ])
+                })
+                .collect();
+
+            let mut units = Map::new();
+            let mut coordinate_properties = Map::new();
+            coordinate_properties.insert(
+                "time".to_string(),
+                lap.points.iter().map(|point| point.time).collect(),
+            );
+            for (channel, values) in lap.properties {
+                units.insert(channel.name.clone(), Value::from(channel.unit.clone()));
+                coordinate_properties.insert(channel.name.clone(), values.into_iter().collect());
+            }
+
+            json!({
+                "type": "Feature",
+                "geometry": {
+                    "type": "LineString",
+                    "coordinates": coordinates,
+                },
+                "properties": {
+                    "lap": lap.number,
+                    "units": units,
+                    "coordinateProperties": coordinate_properties,
+                },
+            })
+        })
+        .collect();
+
+    serde_json::to_writer(
+        writer,
+        &json!({
+            "type": "FeatureCollection",
+            "features": features,
+        }),
+    )?;
+    Ok(())
+}
+
+/// Writes the laps as KML with a `gx:Track` placemark per lap, carrying the
+/// values of the `properties` channels at every point as extended data.
+///
+/// Point times are `start` plus the sample time, or the sample time since
+/// the Unix epoch if the session has no date.
+pub fn write_kml<W: io::Write>(
+    laps: &[LapData],
+    properties: &[String],
+    options: &AlignOptions,
+    name: Option<&str>,
+    start: Option<NaiveDateTime>,
+    mut writer: W,
+) -> Result<()> {
+    let start = start.unwrap_or_default();
+    let map_laps = map_laps(laps, properties, options);
+
+    writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        writer,
+        r#"<kml xmlns="http://www.opengis.net/kml/2.2" xmlns:gx="http://www.google.com/kml/ext/2.2">"#
+    )?;
+    writeln!(writer, " <Document>")?;
+    if let Some(name) = name {
+        writeln!(writer, "  <name>{}</name>", escape(name))?;
+    }
+    writeln!(writer, r#"  <Schema id="channels">"#)?;
+    if let Some(lap) = map_laps.first() {
+        for (channel, _) in &lap.properties {
+            writeln!(
+                writer,
+                r#"   <gx:SimpleArrayField name="{}" type="float"><displayName>{} ({})</displayName></gx:SimpleArrayField>"#,
+                escape(&channel.name),
+                escape(&channel.name),
+                escape(&channel.unit)
+            )?;
+        }
+    }
+    writeln!(writer, "  </Schema>")?;
+
+    for lap in &map_laps {
+        writeln!(writer, "  <Placemark>")?;
+        writeln!(writer, "   <name>Lap {}</name>", lap.number)?;
+        writeln!(writer, "   <gx:Track>")?;
+        writeln!(writer, "    <altitudeMode>absolute</altitudeMode>")?;
+        for point in &lap.points {
+            let time = start + Duration::microseconds((point.time * 1e6).round() as i64);
+            writeln!(
+                writer,
+                "    <when>{}</when>",
+                time.format("%Y-%m-%dT%H:%M:%S%.3f")
+            )?;
+        }
+        for point in &lap.points {
+            writeln!(
+                writer,
+                "    <gx:coord>{:.8} {:.8} {:.2}</gx:coord>",
+                point.position.longitude, point.position.latitude, point.position.altitude
+            )?;
+        }
+        writeln!(writer, "    <ExtendedData>")?;
+        writeln!(writer, r##"     <SchemaData schemaUrl="#channels">"##)?;
+        for (channel, values) in &lap.properties {
+            writeln!(
+                writer,
+                r#"      <gx:SimpleArrayData name="{}">"#,
+                escape(&channel.name)
+            )?;
+            for value in values {
+                match value {
+                    Some(value) => writeln!(writer, "       <gx:value>{}</gx:value>", value)?,
+                    None => writeln!(writer, "       <gx:value/>")?,
+                }
+            }
+            writeln!(writer, "      </gx:SimpleArrayData>")?;
+        }
+        writeln!(writer, "     </SchemaData>")?;
+        writeln!(writer, "    </ExtendedData>")?;
+        writeln!(writer, "   </gx:Track>")?;
+        writeln!(writer, "  </Placemark>")?;
+    }
+
+    writeln!(writer, " </Document>")?;
+    writeln!(writer, "</kml>")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::DataPoint;
+    use crate::geo::Geodetic;
+
+    fn lap() -> LapData {
+        let channel = |name: &str, unit: &str, values: [f64; 2]| ChannelData {
+            name: name.to_string(),
+            unit: unit.to_string(),
+            data: vec![
+                DataPoint {
+                    s: 1.0,
+                    v: values[0],
+                },
+                DataPoint {
+                    s: 1.1,
+                    v: values[1],
+                },
+            ],
+        };
+        let ecef = |longitude: f64| {
+            Geodetic {
+                latitude: 52.3888,
+                longitude,
+                altitude: 12.0,
+            }
+            .to_ecef()
+        };
+        let (start, end) = (ecef(4.5409), ecef(4.5410));
+
+        LapData {
+            lap: 0,
+            channels: vec![
+                channel("ECEF position_X", "m", [start[0], end[0]]),
+                channel("ECEF position_Y", "m", [start[1], end[1]]),
+                channel("ECEF position_Z", "m", [start[2], end[2]]),
+                channel("P_BRK_FRONT", "bar", [0.0, 42.5]),
+            ],
+        }
+    }
+
+    #[test]
+    fn geojson_has_a_line_per_lap_with_channel_values_per_point() {
+        let mut output = Vec::new();
+
+        write_geojson(
+            &[lap()],
+            &["P_BRK_FRONT".to_string()],
+            &AlignOptions::default(),
+            &mut output,
+        )
+        .unwrap();
+
+        let geojson: Value = serde_json::from_slice(&output).unwrap();
+        let feature = &geojson["features"][0];
+        assert_eq!(feature["geometry"]["type"], "LineString");
+        let coordinates = feature["geometry"]["coordinates"].as_array().unwrap();
+        assert_eq!(coordinates.len(), 2);
+        assert!((coordinates[1][0].as_f64().unwrap() - 4.5410).abs() < 1e-9);
+        assert_eq!(feature["properties"]["lap"], 1);
+        assert_eq!(feature["properties"]["units"]["P_BRK_FRONT"], "bar");
+        assert_eq!(
+            feature["properties"]["coordinateProperties"]["P_BRK_FRONT"],
+            json!([0.0, 42.5])
+        );
+    }
+
+    #[test]
+    fn maps_start_at_the_first_fix() {
+        let mut lap = lap();
+        for channel in &mut lap.channels[..3] {
+            channel.data.insert(0, DataPoint { s: 0.9, v: 0.0 });
+        }
+        let mut output = Vec::new();
+
+        write_geojson(
+            &[lap],
+            &["P_BRK_FRONT".to_string()],
+            &AlignOptions::default(),
+            &mut output,
+        )
+        .unwrap();
+
+        let geojson: Value = serde_json::from_slice(&output).unwrap();
+        let feature = &geojson["features"][0];
+        let coordinates = feature["geometry"]["coordinates"].as_array().unwrap();
+        assert_eq!(coordinates.len(), 2);
+        assert!((coordinates[0][0].as_f64().unwrap() - 4.5409).abs() < 1e-9);
+        assert_eq!(
+            feature["properties"]["coordinateProperties"]["time"],
+            json!([1.0, 1.1])
+        );
+    }
+
+    #[test]
+    fn kml_has_a_track_per_lap_with_channel_values_per_point() {
+        let mut output = Vec::new();
+
+        write_kml(
+            &[lap()],
+            &["P_BRK_FRONT".to_string()],
+            &AlignOptions::default(),
+            Some("Zandvoort"),
+            None,
+            &mut output,
+        )
+        .unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output.matches("<Placemark>").count(), 1);
+        assert!(output.contains("<when>1970-01-01T00:00:01.100</when>"));
+        assert!(output.contains("<gx:coord>4.54100000 52.38880000 12.00</gx:coord>"));
+        assert!(output.contains(r#"<displayName>P_BRK_FRONT (bar)</displayName>"#));
+        assert!(output.contains("<gx:value>42.5</gx:value>"));
+    }
+}
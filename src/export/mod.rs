@@ -0,0 +1,198 @@
+use crate::catalog::ChannelEntry;
+use crate::error::{Error, Result};
+use crate::source::TelemetrySource;
+use serde::Serialize;
+
+pub mod align;
+#[cfg(feature = "arrow")]
+pub mod arrow;
+#[cfg(feature = "arrow")]
+pub mod columnar;
+pub mod csv;
+pub mod distance;
+pub mod gpx;
+pub mod json;
+pub mod map;
+pub mod motec;
+#[cfg(feature = "parquet")]
+pub mod parquet;
+pub mod track;
+
+pub use self::align::{
+    align_lap, align_nearest, resample, AlignOptions, AlignedLap, Interpolation, TimeBase,
+};
+#[cfg(feature = "arrow")]
+pub use self::arrow::{write_arrow, IpcFormat};
+pub use self::csv::{export_to_csv, write_csv};
+pub use self::distance::DistanceTrace;
+pub use self::gpx::write_gpx;
+pub use self::json::{write_json, write_ndjson, NdjsonRecord};
+pub use self::map::{write_geojson, write_kml};
+pub use self::motec::{write_ld, write_ldx};
+#[cfg(feature = "parquet")]
+pub use self::parquet::write_parquet;
+pub use self::track::{track_points, TrackPoint};
+
+/// File formats channel data can be exported to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// One row per timestamp, all channels aligned.
+    #[default]
+    Csv,
+    /// The unaligned per-lap, per-channel data as a single document.
+    Json,
+    /// The unaligned data as newline-delimited JSON.
+    Ndjson,
+    /// A MoTeC i2 `.ld` file with every channel at its own rate, and an
+    /// `.ldx` file with the lap markers next to it.
+    Motec,
+    /// A GPX track of the GPS positions with a segment per lap.
+    Gpx,
+    /// A GeoJSON line per lap with channel values at every point.
+    Geojson,
+    /// A KML track per lap with channel values at every point.
+    Kml,
+    /// Aligned like CSV as Arrow IPC, with a record batch per lap.
+    #[cfg(feature = "arrow")]
+    Arrow,
+    /// Aligned like CSV, with typed columns and a row group per lap.
+    #[cfg(feature = "parquet")]
+    Parquet,
+}
+
+impl ExportFormat {
+    /// Whether the channels are aligned on a common time base.
+    pub fn is_aligned(&self) -> bool {
+        !self.is_track()
+            && !matches!(
+                self,
+                ExportFormat::Json | ExportFormat::Ndjson | ExportFormat::Motec
+            )
+    }
+
+    /// Whether this is a map of the GPS positions.
+    pub fn is_track(&self) -> bool {
+        matches!(
+            self,
+            ExportFormat::Gpx | ExportFormat::Geojson | ExportFormat::Kml
+        )
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+            ExportFormat::Ndjson => "ndjson",
+            ExportFormat::Motec => "ld",
+            ExportFormat::Gpx => "gpx",
+            ExportFormat::Geojson => "geojson",
+            ExportFormat::Kml => "kml",
+            #[cfg(feature = "arrow")]
+            ExportFormat::Arrow => "arrow",
+            #[cfg(feature = "parquet")]
+            ExportFormat::Parquet => "parquet",
+        }
+    }
+}
+
+impl std::str::FromStr for ExportFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "csv" => Ok(ExportFormat::Csv),
+            "json" => Ok(ExportFormat::Json),
+            "ndjson" => Ok(ExportFormat::Ndjson),
+            "motec" => Ok(ExportFormat::Motec),
+            "gpx" => Ok(ExportFormat::Gpx),
+            "geojson" => Ok(ExportFormat::Geojson),
+            "kml" => Ok(ExportFormat::Kml),
+            #[cfg(feature = "arrow")]
+            "arrow" => Ok(ExportFormat::Arrow),
+            #[cfg(feature = "parquet")]
+            "parquet" => Ok(ExportFormat::Parquet),
+            _ => Err(Error::InvalidArgument(format!(
+                "unknown or disabled export format '{}'",
+                s
+            ))),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ExportData {
+    pub laps: Vec<LapData>,
+}
+
+#[derive(Serialize)]
+pub struct LapData {
+    /// Lap index, starting at 0; serialized as the lap number, starting at 1.
+    #[serde(serialize_with = "serialize_lap_number")]
+    pub lap: usize,
+    pub channels: Vec<ChannelData>,
+}
+
+fn serialize_lap_number<S: serde::Serializer>(
+    lap: &usize,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    serializer.serialize_u64(*lap as u64 + 1)
+}
+
+#[derive(Serialize)]
+pub struct ChannelData {
+    pub name: String,
+    pub unit: String,
+    pub data: Vec<DataPoint>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct DataPoint {
+    pub s: f64,
+    pub v: f64,
+}
+
+// Data channels (sensors) come at various frequencies, so by default we align everything against the GPS samples.
+pub const MASTER_CHANNEL_NAME: &str = "ECEF position_X";
+
+/// Collects the per-lap data of the given channels for the given lap indices.
+pub fn collect(
+    source: &dyn TelemetrySource,
+    entries: &[&ChannelEntry],
+    laps: &[usize],
+) -> ExportData {
+    let mut export_data = ExportData { laps: Vec::new() };
+
+    for &lap_index in laps {
+        let channel_data_list = entries
+            .iter()
+            .map(|entry| {
+                let channel_data = entry.lap_samples(source, lap_index);
+
+                let data_points = channel_data
+                    .timestamps()
+                    .iter()
+                    .zip(channel_data.samples().iter())
+                    .map(|(&seconds, &value)| DataPoint {
+                        s: seconds,
+                        v: value,
+                    })
+                    .collect();
+
+                ChannelData {
+                    name: entry.name.clone(),
+                    unit: entry.unit.clone(),
+                    data: data_points,
+                }
+            })
+            .collect();
+
+        // Store the lap data
+        export_data.laps.push(LapData {
+            lap: lap_index,
+            channels: channel_data_list,
+        });
+    }
+
+    export_data
+}
@@ -0,0 +1,409 @@
+//! MoTeC i2 `.ld` log files and the `.ldx` files holding their lap markers.
+//!
+//! The `.ld` layout is not documented by MoTeC; this follows the layout of the
+//! files written by their ADL loggers as far as i2 needs it. Every channel is
+//! stored as 32 bit floats at its own rate, from the start of the first
+//! exported lap to the end of the last one.
+
+use super::align::{resample, AlignOptions, Interpolation};
+use super::LapData;
+use crate::error::Result;
+use crate::source::TelemetrySource;
+use std::io;
+
+const HEADER_SIZE: usize = 1762;
+const EVENT_SIZE: usize = 1154;
+const VENUE_SIZE: usize = 1100;
+const VEHICLE_SIZE: usize = 260;
+const CHANNEL_SIZE: usize = 124;
+
+/// A channel resampled at a fixed rate, as MoTeC stores them.
+struct LdChannel {
+    name: String,
+    unit: String,
+    rate: u16,
+    values: Vec<f32>,
+}
+
+/// Little-endian encoding of the fixed size `.ld` records.
+#[derive(Default)]
+struct LdBuffer(Vec<u8>);
+
+impl LdBuffer {
+    fn u16(&mut self, value: u16) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn i16(&mut self, value: i16) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn zeros(&mut self, len: usize) {
+        self.0.resize(self.0.len() + len, 0);
+    }
+
+    /// A string in a field of `len` bytes, truncated and encoded as Latin-1
+    /// (so units like °C survive), padded with zeros.
+    fn str(&mut self, value: &str, len: usize) {
+        let bytes: Vec<u8> = value
+            .chars()
+            .map(|c| u8::try_from(u32::from(c)).unwrap_or(b'?'))
+            .take(len)
+            .collect();
+        self.0.extend_from_slice(&bytes);
+        self.zeros(len - bytes.len());
+    }
+}
+
+/// The rate closest to the median interval between the samples, in Hz.
+fn native_rate(timestamps: &[f64]) -> u16 {
+    let mut intervals: Vec<f64> = timestamps
+        .windows(2)
+        .map(|pair| pair[1] - pair[0])
+        .filter(|&interval| interval > 0.0)
+        .collect();
+    if intervals.is_empty() {
+        return 1;
+    }
+
+    intervals.sort_by(f64::total_cmp);
+    let median = intervals[intervals.len() / 2];
+    (1.0 / median).round().clamp(1.0, f64::from(u16::MAX)) as u16
+}
+
+/// Start and end of the exported laps.
+fn span(source: &dyn TelemetrySource, laps: &[LapData]) -> (f64, f64) {
+    let infos: Vec<_> = laps
+        .iter()
+        .filter_map(|lap| source.lap_info(lap.lap))
+        .collect();
+    match (infos.first(), infos.last()) {
+        (Some(first), Some(last)) => (first.start(), last.end()),
+        _ => (0.0, 0.0),
+    }
+}
+
+fn ld_channels(laps: &[LapData], start: f64, end: f64) -> Vec<LdChannel> {
+    let Some(first_lap) = laps.first() else {
+        return Vec::new();
+    };
+    let options = AlignOptions {
+        interpolation: Interpolation::Linear,
+        ..AlignOptions::default()
+    };
+
+    first_lap
+        .channels
+        .iter()
+        .enumerate()
+        .map(|(index, channel)| {
+            let (times, values): (Vec<f64>, Vec<f64>) = laps
+                .iter()
+                .flat_map(|lap| lap.channels[index].data.iter())
+                .map(|dp| (dp.s, dp.v))
+                .unzip();
+
+            let rate = native_rate(&times);
+            let count = ((end - start) * f64::from(rate)).floor().max(0.0) as usize;
+            let grid: Vec<f64> = (0..count)
+                .map(|step| start + step as f64 / f64::from(rate))
+                .collect();
+
+            LdChannel {
+                name: channel.name.clone(),
+                unit: channel.unit.clone(),
+                rate,
+                values: resample(&grid, &times, &values, &options)
+                    .into_iter()
+                    .map(|value| value.unwrap_or(0.0) as f32)
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+/// Writes the laps as a MoTeC `.ld` file, with the racer, vehicle, track and
+/// date of the session in its header.
+pub fn write_ld<W: io::Write>(
+    source: &dyn TelemetrySource,
+    laps: &[LapData],
+    mut writer: W,
+) -> Result<()> {
+    let (start, end) = span(source, laps);
+    let channels = ld_channels(laps, start, end);
+
+    let event_ptr = HEADER_SIZE;
+    let venue_ptr = event_ptr + EVENT_SIZE;
+    let vehicle_ptr = venue_ptr + VENUE_SIZE;
+    let meta_ptr = vehicle_ptr + VEHICLE_SIZE;
+    let data_ptr = meta_ptr + channels.len() * CHANNEL_SIZE;
+
+    let track = source.track().unwrap_or_default();
+    let vehicle = source.vehicle().unwrap_or_default();
+    let datetime = source.datetime();
+
+    let mut ld = LdBuffer::default();
+
+    // Header
+    ld.u32(0x40);
+    ld.zeros(4);
+    ld.u32(if channels.is_empty() { 0 } else { meta_ptr } as u32);
+    ld.u32(data_ptr as u32);
+    ld.zeros(20);
+    ld.u32(event_ptr as u32);
+    ld.zeros(24);
+    ld.u16(1);
+    ld.u16(0x4240);
+    ld.u16(0xf);
+    ld.u32(0x1f44);
+    ld.str("ADL", 8);
+    ld.u16(420);
+    ld.u16(0xadb0);
+    ld.u32(channels.len() as u32);
+    ld.zeros(4);
+    ld.str(
+        &datetime
+            .map(|datetime| datetime.format("%d/%m/%Y").to_string())
+            .unwrap_or_default(),
+        16,
+    );
+    ld.zeros(16);
+    ld.str(
+        &datetime
+            .map(|datetime| datetime.format("%H:%M:%S").to_string())
+            .unwrap_or_default(),
+        16,
+    );
+    ld.zeros(16);
+    ld.str(&source.racer().unwrap_or_default(), 64);
+    ld.str(&vehicle, 64);
+    ld.zeros(64);
+    ld.str(&track, 64);
+    ld.zeros(64);
+    ld.zeros(1024);
+    // Marks the file as written with "pro logging".
+    ld.u32(0xc81a4);
+    ld.zeros(66);
+    ld.str(&source.championship().unwrap_or_default(), 64);
+    ld.zeros(126);
+
+    // Event
+    ld.str(&source.championship().unwrap_or_default(), 64);
+    ld.str("", 64);
+    ld.str("", 1024);
+    ld.u16(venue_ptr as u16);
+
+    // Venue
+    ld.str(&track, 64);
+    ld.zeros(1034);
+    ld.u16(vehicle_ptr as u16);
+
+    // Vehicle
+    ld.str(&vehicle, 64);
+    ld.zeros(128);
+    ld.u32(0);
+    ld.str("", 32);
+    ld.str("", 32);
+
+    // Channel metadata, a linked list
+    let mut channel_data_ptr = data_ptr;
+    for (index, channel) in channels.iter().enumerate() {
+        let this_ptr = meta_ptr + index * CHANNEL_SIZE;
+        let previous_ptr = if index == 0 {
+            0
+        } else {
+            this_ptr - CHANNEL_SIZE
+        };
+        let next_ptr = if index + 1 == channels.len() {
+            0
+        } else {
+            this_ptr + CHANNEL_SIZE
+        };
+
+        ld.u32(previous_ptr as u32);
+        ld.u32(next_ptr as u32);
+        ld.u32(channel_data_ptr as u32);
+        ld.u32(channel.values.len() as u32);
+        ld.u16(0x2ee1_u16.wrapping_add(index as u16));
+        // 32 bit float
+        ld.u16(0x07);
+        ld.u16(4);
+        ld.u16(channel.rate);
+        // shift, multiplier, scale and decimal places
+        ld.i16(0);
+        ld.i16(1);
+        ld.i16(1);
+        ld.i16(0);
+        ld.str(&channel.name, 32);
+        ld.str(&channel.name, 8);
+        ld.str(&channel.unit, 12);
+        ld.zeros(40);
+
+        channel_data_ptr += channel.values.len() * 4;
+    }
+
+    // Channel data
+    for channel in &channels {
+        for value in &channel.values {
+            ld.0.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    writer.write_all(&ld.0)?;
+    Ok(())
+}
+
+/// Writes the `.ldx` file that goes with [`write_ld`], with a beacon marker at
+/// the end of every lap and the fastest lap in its details.
+pub fn write_ldx<W: io::Write>(
+    source: &dyn TelemetrySource,
+    laps: &[LapData],
+    mut writer: W,
+) -> Result<()> {
+    let (start, _) = span(source, laps);
+    let infos: Vec<_> = laps
+        .iter()
+        .filter_map(|lap| source.lap_info(lap.lap))
+        .collect();
+
+    writeln!(writer, r#"<?xml version="1.0"?>"#)?;
+    writeln!(
+        writer,
+        r#"<LDXFile Locale="English_United Kingdom.1252" DefaultLocale="C" Version="1.6">"#
+    )?;
+    writeln!(writer, " <Layers>")?;
+    writeln!(writer, "  <Layer>")?;
+    writeln!(writer, "   <MarkerBlock>")?;
+    writeln!(writer, r#"    <MarkerGroup Name="Beacons" Index="3">"#)?;
+    for (index, info) in infos.iter().enumerate() {
+        writeln!(
+            writer,
+            r#"     <Marker Version="100" ClassName="BCN" Name="Manual.{}" Flags="77" Time="{:.3}"/>"#,
+            index + 1,
+            (info.end() - start) * 1e6
+        )?;
+    }
+    writeln!(writer, "    </MarkerGroup>")?;
+    writeln!(writer, "   </MarkerBlock>")?;
+    writeln!(writer, "   <RangeBlock/>")?;
+    writeln!(writer, "  </Layer>")?;
+    writeln!(writer, "  <Details>")?;
+    writeln!(
+        writer,
+        r#"   <String Id="Total Laps" Value="{}"/>"#,
+        infos.len()
+    )?;
+    if let Some(fastest) = infos.iter().min_by(|a, b| a.time().total_cmp(&b.time())) {
+        writeln!(
+            writer,
+            r#"   <String Id="Fastest Time" Value="{}:{:06.3}"/>"#,
+            (fastest.time() / 60.0).floor() as u64,
+            fastest.time() % 60.0
+        )?;
+        writeln!(
+            writer,
+            r#"   <String Id="Fastest Lap" Value="{}"/>"#,
+            fastest.number()
+        )?;
+    }
+    writeln!(writer, "  </Details>")?;
+    writeln!(writer, " </Layers>")?;
+    writeln!(writer, "</LDXFile>")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::ChannelCatalog;
+    use crate::source::{ChannelFamily, MemorySource};
+    use chrono::NaiveDate;
+
+    fn session() -> MemorySource {
+        let timestamps: Vec<f64> = (0..200).map(|i| i as f64 / 10.0).collect();
+
+        MemorySource::new()
+            .with_racer("Driver")
+            .with_vehicle("Clio")
+            .with_track("Zandvoort")
+            .with_datetime(
+                NaiveDate::from_ymd_opt(2024, 5, 1)
+                    .unwrap()
+                    .and_hms_opt(14, 30, 0)
+                    .unwrap(),
+            )
+            .with_lap(0.0, 10.0)
+            .with_lap(10.0, 9.5)
+            .with_channel(
+                ChannelFamily::Regular,
+                "Oil Temp",
+                "°C",
+                timestamps.clone(),
+                timestamps.iter().map(|t| 90.0 + t).collect(),
+            )
+    }
+
+    fn u32_at(ld: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(ld[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn u16_at(ld: &[u8], offset: usize) -> u16 {
+        u16::from_le_bytes(ld[offset..offset + 2].try_into().unwrap())
+    }
+
+    #[test]
+    fn ld_holds_the_session_and_channels_at_their_rate() {
+        let source = session();
+        let catalog = ChannelCatalog::build(&source);
+        let entries: Vec<_> = catalog.iter().collect();
+        let data = crate::export::collect(&source, &entries, &[0, 1]);
+        let mut ld = Vec::new();
+
+        write_ld(&source, &data.laps, &mut ld).unwrap();
+
+        assert_eq!(&ld[0x5e..0x68], b"01/05/2024");
+        assert_eq!(&ld[0x9e..0xa4], b"Driver");
+        assert_eq!(u32_at(&ld, 0x56), 1);
+
+        let meta = u32_at(&ld, 0x08) as usize;
+        assert_eq!(&ld[meta + 32..meta + 40], b"Oil Temp");
+        assert_eq!(&ld[meta + 72..meta + 74], b"\xb0C");
+        assert_eq!(u16_at(&ld, meta + 22), 10);
+
+        let count = u32_at(&ld, meta + 12) as usize;
+        assert_eq!(count, 195);
+        let data = u32_at(&ld, meta + 8) as usize;
+        assert_eq!(ld.len(), data + count * 4);
+        let value = |index: usize| {
+            f32::from_le_bytes(
+                ld[data + index * 4..data + index * 4 + 4]
+                    .try_into()
+                    .unwrap(),
+            )
+        };
+        assert_eq!(value(0), 90.0);
+        assert_eq!(value(150), 105.0);
+    }
+
+    #[test]
+    fn ldx_marks_the_end_of_every_lap() {
+        let source = session();
+        let catalog = ChannelCatalog::build(&source);
+        let entries: Vec<_> = catalog.iter().collect();
+        let data = crate::export::collect(&source, &entries, &[0, 1]);
+        let mut ldx = Vec::new();
+
+        write_ldx(&source, &data.laps, &mut ldx).unwrap();
+
+        let ldx = String::from_utf8(ldx).unwrap();
+        assert!(ldx.contains(r#"Name="Manual.1" Flags="77" Time="10000000.000""#));
+        assert!(ldx.contains(r#"Name="Manual.2" Flags="77" Time="19500000.000""#));
+        assert!(ldx.contains(r#"<String Id="Fastest Time" Value="0:09.500"/>"#));
+        assert!(ldx.contains(r#"<String Id="Fastest Lap" Value="2"/>"#));
+    }
+}
@@ -0,0 +1,111 @@
+use super::align::AlignOptions;
+use super::{columnar, LapData};
+use crate::error::Result;
+use ::parquet::arrow::ArrowWriter;
+use ::parquet::basic::Compression;
+use ::parquet::file::properties::WriterProperties;
+use std::io;
+use std::sync::Arc;
+
+/// Writes the laps as Parquet with the channels aligned as for CSV, one row
+/// group per lap. Units are kept in the field metadata of the Arrow schema.
+pub fn write_parquet<W: io::Write + Send>(
+    laps: &[LapData],
+    options: &AlignOptions,
+    writer: W,
+) -> Result<()> {
+    let schema = Arc::new(columnar::schema(laps, options));
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .set_max_row_group_size(usize::MAX)
+        .build();
+    let mut writer = ArrowWriter::try_new(writer, schema.clone(), Some(properties))?;
+
+    for lap in laps {
+        let batch = columnar::lap_batch(&schema, lap, options)?;
+        writer.write(&batch)?;
+        // Closes the row group, so every lap gets its own.
+        writer.flush()?;
+    }
+
+    writer.close()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::{ChannelData, DataPoint};
+    use ::parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    fn lap(lap: usize, start: f64) -> LapData {
+        let channel = |name: &str, unit: &str, times: &[f64]| ChannelData {
+            name: name.to_string(),
+            unit: unit.to_string(),
+            data: times
+                .iter()
+                .map(|&t| DataPoint {
+                    s: start + t,
+                    v: t * 10.0,
+                })
+                .collect(),
+        };
+
+        LapData {
+            lap,
+            channels: vec![
+                channel("ECEF position_X", "m", &[0.0, 1.0, 2.0]),
+                channel("Oil Temp", "°C", &[0.0, 2.0]),
+            ],
+        }
+    }
+
+    #[test]
+    fn laps_become_row_groups_with_units_in_the_metadata() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let options = AlignOptions {
+            tolerance: Some(0.5),
+            ..AlignOptions::default()
+        };
+        write_parquet(
+            &[lap(0, 0.0), lap(1, 3.0)],
+            &options,
+            file.reopen().unwrap(),
+        )
+        .unwrap();
+
+        let builder = ParquetRecordBatchReaderBuilder::try_new(file.reopen().unwrap()).unwrap();
+        assert_eq!(builder.metadata().num_row_groups(), 2);
+        assert_eq!(builder.metadata().row_group(1).num_rows(), 3);
+
+        let schema = builder.schema().clone();
+        let oil = schema.field_with_name("Oil Temp").unwrap();
+        assert_eq!(oil.data_type(), &arrow_schema::DataType::Float64);
+        assert_eq!(
+            oil.metadata()
+                .get(columnar::UNIT_METADATA_KEY)
+                .map(String::as_str),
+            Some("°C")
+        );
+
+        let batches: Vec<_> = builder
+            .build()
+            .unwrap()
+            .map(|batch| batch.unwrap())
+            .collect();
+        let oil_values: Vec<_> = batches
+            .iter()
+            .flat_map(|batch| {
+                batch
+                    .column_by_name("Oil Temp")
+                    .unwrap()
+                    .as_any()
+                    .downcast_ref::<arrow_array::Float64Array>()
+                    .unwrap()
+                    .iter()
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        assert_eq!(&oil_values[3..], &[Some(0.0), None, Some(20.0)]);
+    }
+}
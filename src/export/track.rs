@@ -0,0 +1,77 @@
+//! The path driven in a lap, from the GPS channels.
+
+use super::align::{resample, AlignOptions};
+use super::distance::{POSITION_CHANNEL_NAMES, SPEED_CHANNEL_NAME};
+use super::{ChannelData, LapData};
+use crate::geo::{is_fix, Geodetic};
+use crate::units::Conversion;
+
+/// Position of the vehicle at a sample of the GPS module.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackPoint {
+    /// Seconds since the start of the recording.
+    pub time: f64,
+    pub position: Geodetic,
+    /// Ground speed in m/s, if the lap has the GPS speed.
+    pub speed: Option<f64>,
+}
+
+/// Channel values at the timestamps of `times`, converted with `conversion`.
+fn values_at(times: &[f64], channel: &ChannelData, conversion: Conversion) -> Vec<Option<f64>> {
+    let (channel_times, values): (Vec<f64>, Vec<f64>) =
+        channel.data.iter().map(|dp| (dp.s, dp.v)).unzip();
+
+    resample(times, &channel_times, &values, &AlignOptions::default())
+        .into_iter()
+        .map(|value| value.map(|value| conversion.apply(value)))
+        .collect()
+}
+
+/// The track points of a lap, one per sample of `ECEF position_X` from the
+/// first GPS fix on, or `None` if the lap lacks any of the ECEF positions.
+pub fn track_points(lap: &LapData) -> Option<Vec<TrackPoint>> {
+    let channel = |name: &str| lap.channels.iter().find(|channel| channel.name == name);
+    let [Some(x), Some(y), Some(z)] = POSITION_CHANNEL_NAMES.map(channel) else {
+        return None;
+    };
+
+    let times: Vec<f64> = x.data.iter().map(|dp| dp.s).collect();
+    let to_metres = |channel: &ChannelData| Conversion::between(&channel.unit, "m");
+    let xs = values_at(&times, x, to_metres(x)?);
+    let ys = values_at(&times, y, to_metres(y)?);
+    let zs = values_at(&times, z, to_metres(z)?);
+    let speeds = channel(SPEED_CHANNEL_NAME).and_then(|speed| {
+        Some(values_at(
+            &times,
+            speed,
+            Conversion::between(&speed.unit, "m/s")?,
+        ))
+    });
+
+    let points = (0..times.len())
+        .filter_map(|i| {
+            let position = [xs[i]?, ys[i]?, zs[i]?];
+            let [x, y, z] = position;
+            is_fix(position).then(|| TrackPoint {
+                time: times[i],
+                position: Geodetic::from_ecef(x, y, z),
+                speed: speeds.as_ref().and_then(|speeds| speeds[i]),
+            })
+        })
+        .collect();
+
+    Some(points)
+}
+
+/// Values of a channel at the track points, as aligned for CSV.
+pub fn values_at_points(
+    points: &[TrackPoint],
+    channel: &ChannelData,
+    options: &AlignOptions,
+) -> Vec<Option<f64>> {
+    let times: Vec<f64> = points.iter().map(|point| point.time).collect();
+    let (channel_times, values): (Vec<f64>, Vec<f64>) =
+        channel.data.iter().map(|dp| (dp.s, dp.v)).unzip();
+
+    resample(&times, &channel_times, &values, options)
+}
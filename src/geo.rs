@@ -0,0 +1,139 @@
+//! Conversions between the ECEF positions the GPS module records and WGS84
+//! latitude, longitude and altitude, or local east, north and up axes.
+
+/// Semi-major axis of the WGS84 ellipsoid, in metres.
+const SEMI_MAJOR_AXIS: f64 = 6_378_137.0;
+/// Flattening of the WGS84 ellipsoid.
+const FLATTENING: f64 = 1.0 / 298.257_223_563;
+/// Square of the first eccentricity.
+const ECCENTRICITY_SQUARED: f64 = FLATTENING * (2.0 - FLATTENING);
+
+/// A WGS84 position; latitude and longitude in degrees, altitude in metres
+/// above the ellipsoid.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Geodetic {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub altitude: f64,
+}
+
+impl Geodetic {
+    /// Converts an ECEF position in metres.
+    ///
+    /// Iterates on the latitude, which converges to well below a millimetre
+    /// within a few rounds anywhere near the surface.
+    pub fn from_ecef(x: f64, y: f64, z: f64) -> Self {
+        let p = x.hypot(y);
+        let longitude = y.atan2(x);
+        let mut latitude = z.atan2(p * (1.0 - ECCENTRICITY_SQUARED));
+
+        for _ in 0..5 {
+            let sin = latitude.sin();
+            let n = SEMI_MAJOR_AXIS / (1.0 - ECCENTRICITY_SQUARED * sin * sin).sqrt();
+            latitude = (z + ECCENTRICITY_SQUARED * n * sin).atan2(p);
+        }
+
+        let (sin, cos) = latitude.sin_cos();
+        let altitude =
+            p * cos + z * sin - SEMI_MAJOR_AXIS * (1.0 - ECCENTRICITY_SQUARED * sin * sin).sqrt();
+
+        Self {
+            latitude: latitude.to_degrees(),
+            longitude: longitude.to_degrees(),
+            altitude,
+        }
+    }
+
+    /// The ECEF position in metres.
+    pub fn to_ecef(&self) -> [f64; 3] {
+        let (sin_lat, cos_lat) = self.latitude.to_radians().sin_cos();
+        let (sin_lon, cos_lon) = self.longitude.to_radians().sin_cos();
+        let n = SEMI_MAJOR_AXIS / (1.0 - ECCENTRICITY_SQUARED * sin_lat * sin_lat).sqrt();
+
+        [
+            (n + self.altitude) * cos_lat * cos_lon,
+            (n + self.altitude) * cos_lat * sin_lon,
+            (n * (1.0 - ECCENTRICITY_SQUARED) + self.altitude) * sin_lat,
+        ]
+    }
+}
+
+/// Whether an ECEF position is an actual fix: the GPS module records all-zero
+/// positions until it has one.
+pub fn is_fix(position: [f64; 3]) -> bool {
+    position.iter().all(|value| value.is_finite()) && position.iter().any(|&value| value != 0.0)
+}
+
+/// East, north and up components of an ECEF vector (a displacement or a
+/// velocity) in the local frame at `origin`.
+pub fn ecef_to_enu(vector: [f64; 3], origin: &Geodetic) -> [f64; 3] {
+    let [x, y, z] = vector;
+    let (sin_lat, cos_lat) = origin.latitude.to_radians().sin_cos();
+    let (sin_lon, cos_lon) = origin.longitude.to_radians().sin_cos();
+
+    [
+        -sin_lon * x + cos_lon * y,
+        -sin_lat * cos_lon * x - sin_lat * sin_lon * y + cos_lat * z,
+        cos_lat * cos_lon * x + cos_lat * sin_lon * y + sin_lat * z,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() < tolerance,
+            "{} is not within {} of {}",
+            actual,
+            tolerance,
+            expected
+        );
+    }
+
+    #[test]
+    fn points_on_the_axes() {
+        let equator = Geodetic::from_ecef(SEMI_MAJOR_AXIS, 0.0, 0.0);
+        assert_close(equator.latitude, 0.0, 1e-9);
+        assert_close(equator.longitude, 0.0, 1e-9);
+        assert_close(equator.altitude, 0.0, 1e-6);
+
+        let pole = Geodetic::from_ecef(0.0, 0.0, 6_356_752.314_245 + 100.0);
+        assert_close(pole.latitude, 90.0, 1e-9);
+        assert_close(pole.altitude, 100.0, 1e-3);
+    }
+
+    #[test]
+    fn enu_axes_at_the_equator() {
+        let origin = Geodetic {
+            latitude: 0.0,
+            longitude: 0.0,
+            altitude: 0.0,
+        };
+
+        assert_eq!(ecef_to_enu([1.0, 0.0, 0.0], &origin), [0.0, 0.0, 1.0]);
+        assert_eq!(ecef_to_enu([0.0, 1.0, 0.0], &origin), [1.0, 0.0, 0.0]);
+        assert_eq!(ecef_to_enu([0.0, 0.0, 1.0], &origin), [0.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn round_trips_through_ecef() {
+        // Zandvoort and Interlagos, on either side of the equator and meridian.
+        for (latitude, longitude, altitude) in
+            [(52.3888, 4.5409, 12.0), (-23.7036, -46.6997, 760.0)]
+        {
+            let [x, y, z] = Geodetic {
+                latitude,
+                longitude,
+                altitude,
+            }
+            .to_ecef();
+            let position = Geodetic::from_ecef(x, y, z);
+
+            assert_close(position.latitude, latitude, 1e-9);
+            assert_close(position.longitude, longitude, 1e-9);
+            assert_close(position.altitude, altitude, 1e-4);
+        }
+    }
+}
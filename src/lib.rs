@@ -0,0 +1,22 @@
+//! Reading AiM `xrk` data files.
+//!
+//! The `xrk-cli` binary is a thin front-end on top of this crate; other tools
+//! can use it directly to load sessions, enumerate channels, slice laps and
+//! build or write aligned exports.
+
+pub mod catalog;
+pub mod commands;
+pub mod config;
+pub mod error;
+pub mod export;
+pub mod geo;
+pub mod selection;
+pub mod source;
+pub mod units;
+
+pub use catalog::{ChannelCatalog, ChannelEntry};
+pub use error::{Error, Result};
+pub use selection::{ChannelSelection, LapSelection};
+pub use source::{
+    load, load_with, Backend, ChannelFamily, ChannelSamples, LapInfo, TelemetrySource,
+};
@@ -1,24 +1,78 @@
-use clap::{arg, command, value_parser, Arg, ArgAction, Command};
-use std::collections::HashSet;
+use clap::{arg, command, value_parser, Arg, ArgAction, ArgMatches, Command};
 use std::path::Path;
 use std::path::PathBuf;
-use xdrk::Run;
 
-mod commands;
+use xrk_cli::commands::export::ExportOptions;
+use xrk_cli::commands::output::OutputFormat;
+use xrk_cli::config::Config;
+use xrk_cli::export::{AlignOptions, ExportFormat, Interpolation, NdjsonRecord, TimeBase};
+use xrk_cli::source::{window, ConvertedSource, DerivedSource, TimeWindow, WindowedSource};
+use xrk_cli::units::{UnitPreferences, UnitSystem};
+use xrk_cli::{commands, Backend, ChannelSelection, Error, LapSelection, Result};
 
 fn main() {
+    if let Err(err) = run() {
+        eprintln!("Error: {}", err);
+        std::process::exit(err.exit_code());
+    }
+}
+
+fn run() -> Result<()> {
     let matches = command!() // requires `cargo` feature
         .subcommand_required(true)
         .about("XRK Data Reader")
         .arg(
             arg!(
-                -f --file <FILE> "Data file to load"
+                -f --file <FILE> "Data file to load (.xrk, .drk or compressed .xrz)"
             )
             .required(true)
             .value_parser(value_parser!(PathBuf)),
         )
+        .arg(
+            Arg::new("backend")
+                .long("backend")
+                .value_name("BACKEND")
+                .help("Decoder to load the file with (xdrk, native)")
+                .value_parser(|s: &str| s.parse::<Backend>()),
+        )
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .value_name("FILE")
+                .help("Configuration file with channel aliases and units [default: ~/.config/xrk-cli/config.toml]")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("units")
+                .long("units")
+                .value_name("SYSTEM")
+                .global(true)
+                .help("Convert channel values to a unit system (metric, imperial, si)")
+                .value_parser(|s: &str| s.parse::<UnitSystem>()),
+        )
+        .arg(
+            Arg::new("unit")
+                .long("unit")
+                .value_name("CHANNEL=UNIT")
+                .global(true)
+                .action(ArgAction::Append)
+                .help("Convert a channel to the given unit, e.g. \"GPS Speed=m/s\" (repeatable)")
+                .value_parser(parse_unit_override),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .value_name("FORMAT")
+                .global(true)
+                .help("Output format (table, json, yaml, csv; export: csv, json, ndjson, motec, gpx, geojson, kml, arrow, parquet)"),
+        )
         .subcommand(Command::new("info").about("Get session info"))
-        .subcommand(Command::new("lap").about("Preview single lap data for all channels (deprecated)"))
+        .subcommand(
+            Command::new("lap")
+                .about("Preview lap data for all channels (deprecated)")
+                .arg(laps_arg())
+                .args(window_args()),
+        )
         .subcommand(
             Command::new("laps").about("Print lap timings"), // .arg(arg!(-l --list "lists test values").action(ArgAction::SetTrue)),
         )
@@ -28,16 +82,67 @@ fn main() {
                     .long("preview")
                     .help("Give a small preview of datapoints available in channel")
                     .action(ArgAction::SetTrue),
-            ),
+            ).arg(laps_arg()).args(window_args()),
         )
         .subcommand(
-            Command::new("export").about("Export channel data (experimental)").arg(
+            Command::new("export").about("Export channel data (experimental)").arg(laps_arg()).args(window_args()).arg(
                 Arg::new("channels")
                     .short('c')
                     .long("channels")
                     // .takes_value(true)
                     .value_name("CHANNELS")
-                    .help("Comma-separated list of channels to export (e.g., \"Logger Temperature\",P_BRK_FRONT)"),
+                    .help("Comma-separated channel names, globs, re:regexes or aliases to export (e.g., \"logger temperature\",'GPS *',re:^P_BRK)"),
+            ).arg(
+                Arg::new("records")
+                    .long("records")
+                    .value_name("RECORD")
+                    .help("What each NDJSON line holds (channel, sample)")
+                    .value_parser(|s: &str| s.parse::<NdjsonRecord>()),
+            ).arg(
+                Arg::new("master")
+                    .long("master")
+                    .value_name("CHANNEL")
+                    .help("Channel whose timestamps the CSV rows follow [default: ECEF position_X if present]"),
+            ).arg(
+                Arg::new("rate")
+                    .long("rate")
+                    .value_name("HZ")
+                    .conflicts_with("master")
+                    .help("Resample the CSV rows onto a uniform time grid at this rate")
+                    .value_parser(parse_rate),
+            ).arg(
+                Arg::new("distance")
+                    .long("distance")
+                    .value_name("METRES")
+                    .num_args(0..=1)
+                    .default_missing_value("1")
+                    .conflicts_with_all(["master", "rate"])
+                    .help("Resample the CSV rows every METRES driven, adding a distance column [default: 1]")
+                    .value_parser(parse_distance_step),
+            ).arg(
+                Arg::new("interp")
+                    .long("interp")
+                    .value_name("MODE")
+                    .help("Interpolation of CSV values (nearest, linear, previous, cubic, none) [default: nearest]")
+                    .value_parser(|s: &str| s.parse::<Interpolation>()),
+            ).arg(
+                Arg::new("tolerance")
+                    .long("tolerance")
+                    .value_name("SECONDS")
+                    .help("Leave CSV values empty without a channel sample this close")
+                    .value_parser(parse_tolerance),
+            ).arg(
+                Arg::new("output")
+                    .short('o')
+                    .long("output")
+                    .value_name("PATH")
+                    .help("File to write to, - for stdout (default: export.<format>)")
+                    .value_parser(value_parser!(PathBuf)),
+            ).arg(
+                Arg::new("force")
+                    .long("force")
+                    .help("Overwrite the output file if it exists")
+                    .action(ArgAction::SetTrue),
             ),
         )
         .get_matches();
@@ -47,75 +152,237 @@ fn main() {
         .expect("required");
 
     let file_path = Path::new(&data_file);
-    if !file_path.exists() {
-        eprintln!("Error: The file '{}' does not exist.", file_path.display());
-        std::process::exit(1);
+
+    if matches.subcommand_name() == Some("export") {
+        eprintln!("Loading data from file");
     }
 
-    if let Some(matches) = matches.subcommand_matches("info") {
-        match Run::load(file_path) {
-            Ok(run) => {
-                commands::info::display_run_info(&run);
-            }
-            Err(err) => {
-                eprintln!("Failed to load: {}", err);
-            }
+    let config_path = matches.get_one::<PathBuf>("config").map(PathBuf::as_path);
+    let config = Config::load_or_default(config_path)?;
+
+    let mut units = UnitPreferences {
+        system: matches.get_one::<UnitSystem>("units").copied(),
+        overrides: config.units.clone(),
+        ..UnitPreferences::default()
+    };
+    if let Some(overrides) = matches.get_many::<(String, String)>("unit") {
+        for (channel, unit) in overrides {
+            units.overrides.insert(channel.clone(), unit.clone());
+            units.required.insert(channel.clone());
         }
     }
 
-    if let Some(matches) = matches.subcommand_matches("laps") {
-        match Run::load(file_path) {
-            Ok(run) => {
-                commands::laps::display_laps_info(&run);
-            }
-            Err(err) => {
-                eprintln!("Failed to load: {}", err);
-            }
+    let backend = matches
+        .get_one::<Backend>("backend")
+        .copied()
+        .unwrap_or_default();
+
+    let format = matches.get_one::<String>("format");
+    let (output_format, export_format) = match matches.subcommand_name() {
+        Some("export") => (
+            OutputFormat::default(),
+            format
+                .map(|format| format.parse::<ExportFormat>())
+                .transpose()?
+                .unwrap_or_default(),
+        ),
+        _ => (
+            format
+                .map(|format| format.parse::<OutputFormat>())
+                .transpose()?
+                .unwrap_or_default(),
+            ExportFormat::default(),
+        ),
+    };
+
+    if let Some(("export", matches)) = matches.subcommand() {
+        check_export_args(matches, export_format)?;
+    }
+
+    let window = match matches.subcommand() {
+        Some((_, matches)) => {
+            let from = matches.try_get_one::<f64>("from").ok().flatten().copied();
+            let to = matches.try_get_one::<f64>("to").ok().flatten().copied();
+            (from.is_some() || to.is_some())
+                .then(|| TimeWindow::new(from, to))
+                .transpose()?
         }
+        None => None,
+    };
+
+    let mut source: Box<dyn xrk_cli::TelemetrySource> =
+        Box::new(DerivedSource::new(xrk_cli::load_with(file_path, backend)?));
+    if !units.is_empty() {
+        source = Box::new(ConvertedSource::new(source, &units)?);
+    }
+    if let Some(window) = window {
+        source = Box::new(WindowedSource::new(source, window));
     }
 
-    if let Some(matches) = matches.subcommand_matches("channels") {
-        let preview_enabled = matches.get_flag("preview");
+    match matches.subcommand() {
+        Some(("info", _)) => commands::info::display_run_info(source.as_ref(), output_format),
+        Some(("laps", _)) => commands::laps::display_laps_info(source.as_ref(), output_format),
+        Some(("lap", matches)) => commands::lap::display_run_info(
+            source.as_ref(),
+            &matches
+                .get_one::<LapSelection>("laps")
+                .cloned()
+                .unwrap_or_default(),
+            output_format,
+        ),
+        Some(("channels", matches)) => {
+            let preview_enabled = matches.get_flag("preview");
+            commands::channels::display_channels_list(
+                source.as_ref(),
+                preview_enabled,
+                matches.get_one::<LapSelection>("laps"),
+                output_format,
+            )
+        }
+        Some(("export", matches)) => {
+            let channels = match matches.get_one::<String>("channels") {
+                Some(channels) => ChannelSelection::parse(channels, &config.aliases)?,
+                None => ChannelSelection::default(),
+            };
+
+            let options = ExportOptions {
+                channels,
+                laps: matches
+                    .get_one::<LapSelection>("laps")
+                    .cloned()
+                    .unwrap_or_default(),
+                format: export_format,
+                record: matches
+                    .get_one::<NdjsonRecord>("records")
+                    .copied()
+                    .unwrap_or_default(),
+                align: AlignOptions {
+                    time_base: match (
+                        matches.get_one::<String>("master"),
+                        matches.get_one::<f64>("rate"),
+                        matches.get_one::<f64>("distance"),
+                    ) {
+                        (Some(master), _, _) => TimeBase::Master(master.clone()),
+                        (None, Some(&rate), _) => TimeBase::Rate(rate),
+                        (None, None, Some(&step)) => TimeBase::Distance(step),
+                        (None, None, None) => TimeBase::Auto,
+                    },
+                    interpolation: matches
+                        .get_one::<Interpolation>("interp")
+                        .copied()
+                        .unwrap_or_default(),
+                    tolerance: matches.get_one::<f64>("tolerance").copied(),
+                    ..AlignOptions::default()
+                },
+                output: matches.get_one::<PathBuf>("output").cloned(),
+                force: matches.get_flag("force"),
+            };
 
-        match Run::load(file_path) {
-            Ok(run) => {
-                commands::channels::display_channels_list(&run, preview_enabled);
-            }
-            Err(err) => {
-                eprintln!("Failed to load: {}", err);
-            }
+            commands::export::export(source.as_ref(), &options)
         }
+        _ => unreachable!("subcommand is required"),
     }
+}
+
+/// The `--laps` selector shared by the lap-aware commands.
+fn laps_arg() -> Arg {
+    Arg::new("laps")
+        .long("laps")
+        .value_name("LAPS")
+        .help("Laps to include, e.g. 2,4-7, all, best, best:3, last, exclude-outlap, exclude-inlap [default: all]")
+        .value_parser(|s: &str| s.parse::<LapSelection>())
+}
+
+/// The `--from`/`--to` time window shared by the commands reading channel data.
+fn window_args() -> [Arg; 2] {
+    [
+        Arg::new("from")
+            .long("from")
+            .value_name("TIME")
+            .help("Only use data from this time on, in seconds or mm:ss.sss")
+            .value_parser(window::parse_time),
+        Arg::new("to")
+            .long("to")
+            .value_name("TIME")
+            .help("Only use data up to this time, in seconds or mm:ss.sss")
+            .value_parser(window::parse_time),
+    ]
+}
 
-    if let Some(matches) = matches.subcommand_matches("lap") {
-        match Run::load(file_path) {
-            Ok(run) => {
-                commands::lap::display_run_info(&run);
-            }
-            Err(err) => {
-                eprintln!("Failed to load: {}", err);
-            }
+/// Rejects the export options the chosen format has no use for.
+fn check_export_args(matches: &ArgMatches, format: ExportFormat) -> Result<()> {
+    // Maps take channel values at the track points the way the rows are aligned.
+    let map = matches!(format, ExportFormat::Geojson | ExportFormat::Kml);
+    let checks = [
+        (
+            &["records"][..],
+            format == ExportFormat::Ndjson,
+            "ndjson exports",
+        ),
+        (
+            &["master", "rate", "distance"],
+            format.is_aligned(),
+            "csv, arrow and parquet exports",
+        ),
+        (
+            &["interp", "tolerance"],
+            format.is_aligned() || map,
+            "csv, arrow, parquet, geojson and kml exports",
+        ),
+    ];
+
+    for (names, applies, formats) in checks {
+        if let Some(name) = names
+            .iter()
+            .find(|&&name| !applies && matches.contains_id(name))
+        {
+            return Err(Error::InvalidArgument(format!(
+                "--{} only applies to {}",
+                name, formats
+            )));
         }
     }
+    Ok(())
+}
 
-    if let Some(matches) = matches.subcommand_matches("export") {
-        eprintln!("Loading data from file");
+fn parse_rate(s: &str) -> Result<f64> {
+    match s.parse::<f64>() {
+        Ok(rate) if rate.is_finite() && rate > 0.0 => Ok(rate),
+        _ => Err(Error::InvalidArgument(format!(
+            "invalid rate '{}' (expected a positive number of Hz)",
+            s
+        ))),
+    }
+}
+
+fn parse_distance_step(s: &str) -> Result<f64> {
+    match s.parse::<f64>() {
+        Ok(step) if step.is_finite() && step > 0.0 => Ok(step),
+        _ => Err(Error::InvalidArgument(format!(
+            "invalid distance step '{}' (expected a positive number of metres)",
+            s
+        ))),
+    }
+}
+
+fn parse_tolerance(s: &str) -> Result<f64> {
+    match s.parse::<f64>() {
+        Ok(tolerance) if tolerance.is_finite() && tolerance >= 0.0 => Ok(tolerance),
+        _ => Err(Error::InvalidArgument(format!(
+            "invalid tolerance '{}' (expected a non-negative number of seconds)",
+            s
+        ))),
+    }
+}
 
-        let desired_channels: Option<HashSet<&str>> =
-            matches.get_one::<String>("channels").map(|channels_str| {
-                channels_str
-                    .split(',')
-                    .map(|s| s.trim())
-                    .collect::<HashSet<&str>>()
-            });
-
-        match Run::load(file_path) {
-            Ok(run) => {
-                commands::export::export(&run, desired_channels);
-            }
-            Err(err) => {
-                eprintln!("Failed to load: {}", err);
-            }
+fn parse_unit_override(s: &str) -> Result<(String, String)> {
+    match s.split_once('=') {
+        Some((channel, unit)) if !channel.trim().is_empty() && !unit.trim().is_empty() => {
+            Ok((channel.trim().to_string(), unit.trim().to_string()))
         }
+        _ => Err(Error::InvalidArgument(format!(
+            "invalid unit override '{}' (expected CHANNEL=UNIT)",
+            s
+        ))),
     }
 }
@@ -0,0 +1,470 @@
+//! Selecting laps and channels on the command line, e.g. `--laps best:3` or
+//! `--channels 'GPS *,re:^P_BRK'`.
+
+use crate::catalog::{ChannelCatalog, ChannelEntry};
+use crate::error::{Error, Result};
+use crate::source::TelemetrySource;
+use globset::{GlobBuilder, GlobMatcher};
+use regex::{Regex, RegexBuilder};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Term {
+    All,
+    /// Inclusive range of lap numbers (starting at 1).
+    Range(usize, usize),
+    /// The given number of fastest laps.
+    Best(usize),
+    Last,
+    ExcludeOutlap,
+    ExcludeInlap,
+}
+
+/// A set of laps: lap numbers and ranges, `all`, `best`, `best:N`, `last`,
+/// `exclude-outlap` and `exclude-inlap`, separated by commas.
+///
+/// The exclusions are applied first, so `best,exclude-outlap` is the fastest
+/// lap that is not the out lap. Without any lap term, all laps are selected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LapSelection {
+    text: String,
+    terms: Vec<Term>,
+}
+
+impl Default for LapSelection {
+    fn default() -> Self {
+        Self {
+            text: "all".to_string(),
+            terms: vec![Term::All],
+        }
+    }
+}
+
+impl std::str::FromStr for LapSelection {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = |term: &str| {
+            Error::InvalidArgument(format!(
+                "invalid lap selection '{}' (expected e.g. 2,4-7, all, best, best:3, last, exclude-outlap or exclude-inlap)",
+                term
+            ))
+        };
+        let number = |text: &str, term: &str| match text.trim().parse::<usize>() {
+            Ok(number) if number > 0 => Ok(number),
+            _ => Err(invalid(term)),
+        };
+
+        let terms = split_terms(s)
+            .into_iter()
+            .map(str::trim)
+            .map(|term| match term {
+                "all" => Ok(Term::All),
+                "best" => Ok(Term::Best(1)),
+                "last" => Ok(Term::Last),
+                "exclude-outlap" => Ok(Term::ExcludeOutlap),
+                "exclude-inlap" => Ok(Term::ExcludeInlap),
+                _ => {
+                    if let Some(count) = term.strip_prefix("best:") {
+                        Ok(Term::Best(number(count, term)?))
+                    } else if let Some((from, to)) = term.split_once('-') {
+                        let (from, to) = (number(from, term)?, number(to, term)?);
+                        if from > to {
+                            return Err(invalid(term));
+                        }
+                        Ok(Term::Range(from, to))
+                    } else {
+                        let lap = number(term, term)?;
+                        Ok(Term::Range(lap, lap))
+                    }
+                }
+            })
+            .collect::<Result<Vec<Term>>>()?;
+
+        Ok(Self {
+            text: s.to_string(),
+            terms,
+        })
+    }
+}
+
+impl fmt::Display for LapSelection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+impl LapSelection {
+    /// Indices (starting at 0) of the selected laps of the session, in order.
+    ///
+    /// Fails on lap numbers the session does not have.
+    pub fn select(&self, source: &dyn TelemetrySource) -> Result<Vec<usize>> {
+        let count = source.number_of_laps();
+
+        let mut candidates: BTreeSet<usize> = (0..count).collect();
+        for term in &self.terms {
+            match term {
+                Term::ExcludeOutlap => {
+                    candidates.remove(&0);
+                }
+                Term::ExcludeInlap => {
+                    candidates.remove(&count.saturating_sub(1));
+                }
+                _ => {}
+            }
+        }
+
+        let mut selected = BTreeSet::new();
+        let mut has_lap_terms = false;
+
+        for term in &self.terms {
+            match *term {
+                Term::All => selected.extend(candidates.iter().copied()),
+                Term::Range(from, to) => {
+                    if to > count {
+                        return Err(Error::UnknownLap(to));
+                    }
+                    selected.extend((from - 1..to).filter(|lap| candidates.contains(lap)));
+                }
+                Term::Best(laps) => {
+                    // Ranked on the whole lap times, among the laps with any
+                    // time left (e.g. within a time window).
+                    let mut by_time: Vec<(usize, f64)> = candidates
+                        .iter()
+                        .filter(|&&lap| source.lap_info(lap).is_some_and(|info| info.time() > 0.0))
+                        .filter_map(|&lap| source.lap_time(lap).map(|time| (lap, time)))
+                        .collect();
+                    by_time.sort_by(|a, b| a.1.total_cmp(&b.1));
+                    selected.extend(by_time.iter().take(laps).map(|&(lap, _)| lap));
+                }
+                Term::Last => selected.extend(candidates.last().copied()),
+                Term::ExcludeOutlap | Term::ExcludeInlap => continue,
+            }
+            has_lap_terms = true;
+        }
+
+        if !has_lap_terms {
+            selected = candidates;
+        }
+
+        Ok(selected.into_iter().collect())
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Pattern {
+    Name(String),
+    Glob(GlobMatcher),
+    Regex(Regex),
+}
+
+impl Pattern {
+    fn parse(text: &str) -> Result<Self> {
+        let invalid = |message: String| {
+            Error::InvalidArgument(format!("invalid channel pattern '{}': {}", text, message))
+        };
+
+        if let Some(regex) = text.strip_prefix("re:") {
+            RegexBuilder::new(regex)
+                .case_insensitive(true)
+                .build()
+                .map(Pattern::Regex)
+                .map_err(|err| invalid(err.to_string()))
+        } else if text.contains(['*', '?', '[']) {
+            GlobBuilder::new(text)
+                .case_insensitive(true)
+                .backslash_escape(true)
+                .build()
+                .map(|glob| Pattern::Glob(glob.compile_matcher()))
+                .map_err(|err| invalid(err.to_string()))
+        } else {
+            Ok(Pattern::Name(text.replace("\\,", ",").to_lowercase()))
+        }
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        match self {
+            Pattern::Name(pattern) => name.to_lowercase() == *pattern,
+            Pattern::Glob(glob) => glob.is_match(name),
+            Pattern::Regex(regex) => regex.is_match(name),
+        }
+    }
+}
+
+/// Splits `s` on the commas outside brackets and braces, which the globs
+/// (`{a,b}`) and regular expressions (`.{1,3}`) of a term may contain. A
+/// backslash escapes the character after it.
+fn split_terms(s: &str) -> Vec<&str> {
+    let mut terms = Vec::new();
+    let (mut start, mut depth, mut escaped) = (0, 0usize, false);
+
+    for (i, c) in s.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                terms.push(&s[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    terms.push(&s[start..]);
+
+    terms
+}
+
+/// A set of channels: names, globs (`GPS *`) and regular expressions
+/// (`re:^P_BRK`), separated by commas and all matched case-insensitively.
+///
+/// Terms that are an alias expand to the alias' value, which may be a list of
+/// terms itself. Without any term, all channels are selected.
+#[derive(Debug, Clone, Default)]
+pub struct ChannelSelection {
+    patterns: Vec<(String, Pattern)>,
+}
+
+impl ChannelSelection {
+    /// Parses the terms of `s`, expanding the given aliases.
+    pub fn parse(s: &str, aliases: &BTreeMap<String, String>) -> Result<Self> {
+        let alias = |term: &str| {
+            aliases
+                .iter()
+                .find(|(alias, _)| alias.eq_ignore_ascii_case(term))
+                .map(|(_, value)| value.as_str())
+        };
+
+        let patterns = split_terms(s)
+            .into_iter()
+            .map(str::trim)
+            .filter(|term| !term.is_empty())
+            .flat_map(|term| match alias(term) {
+                Some(value) => split_terms(value).into_iter().map(str::trim).collect(),
+                None => vec![term],
+            })
+            .map(|term| Ok((term.to_string(), Pattern::parse(term)?)))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self { patterns })
+    }
+
+    pub fn is_all(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// The selected channels of the catalog, in catalog order.
+    ///
+    /// Fails on the first term that matches no channel.
+    pub fn select<'c>(&self, catalog: &'c ChannelCatalog) -> Result<Vec<&'c ChannelEntry>> {
+        if self.is_all() {
+            return Ok(catalog.iter().collect());
+        }
+
+        for (text, pattern) in &self.patterns {
+            if !catalog.iter().any(|entry| pattern.matches(&entry.name)) {
+                return Err(Error::UnknownChannel {
+                    name: text.clone(),
+                    suggestions: match pattern {
+                        Pattern::Name(name) => close_matches(name, catalog),
+                        _ => Vec::new(),
+                    },
+                });
+            }
+        }
+
+        Ok(catalog
+            .iter()
+            .filter(|entry| {
+                self.patterns
+                    .iter()
+                    .any(|(_, pattern)| pattern.matches(&entry.name))
+            })
+            .collect())
+    }
+}
+
+/// The channel called `name`, ignoring case as `-c` does; an unknown name
+/// fails with the closest channel names.
+pub fn find_channel<'c>(catalog: &'c ChannelCatalog, name: &str) -> Result<&'c ChannelEntry> {
+    let pattern = Pattern::Name(name.to_lowercase());
+
+    catalog
+        .find(name)
+        .or_else(|| catalog.iter().find(|entry| pattern.matches(&entry.name)))
+        .ok_or_else(|| Error::UnknownChannel {
+            name: name.to_string(),
+            suggestions: close_matches(name, catalog),
+        })
+}
+
+/// Up to three channel names resembling `name`, the most similar first.
+fn close_matches(name: &str, catalog: &ChannelCatalog) -> Vec<String> {
+    let name = name.to_lowercase();
+
+    let mut matches: Vec<(f64, &str)> = catalog
+        .iter()
+        .map(|entry| {
+            let candidate = entry.name.to_lowercase();
+            let similarity = if candidate.contains(&name) || name.contains(&candidate) {
+                1.0
+            } else {
+                strsim::jaro_winkler(&name, &candidate)
+            };
+            (similarity, entry.name.as_str())
+        })
+        .filter(|&(similarity, _)| similarity >= 0.8)
+        .collect();
+
+    matches.sort_by(|a, b| b.0.total_cmp(&a.0));
+    matches.dedup_by(|a, b| a.1 == b.1);
+    matches
+        .into_iter()
+        .take(3)
+        .map(|(_, name)| name.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::{ChannelFamily, MemorySource};
+
+    fn session() -> MemorySource {
+        [95.0, 62.5, 61.0, 63.2, 60.4, 61.8, 88.0, 60.9]
+            .iter()
+            .fold((MemorySource::new(), 0.0), |(source, start), &time| {
+                (source.with_lap(start, time), start + time)
+            })
+            .0
+    }
+
+    fn select(selection: &str) -> Result<Vec<usize>> {
+        selection.parse::<LapSelection>()?.select(&session())
+    }
+
+    #[test]
+    fn lists_and_ranges() {
+        assert_eq!(select("2,4-6").unwrap(), vec![1, 3, 4, 5]);
+        assert_eq!(select("4-6, 2,5").unwrap(), vec![1, 3, 4, 5]);
+        assert_eq!(select("all").unwrap(), (0..8).collect::<Vec<_>>());
+        assert!(matches!(select("7-9"), Err(Error::UnknownLap(9))));
+    }
+
+    #[test]
+    fn best_and_last() {
+        assert_eq!(select("best").unwrap(), vec![4]);
+        assert_eq!(select("best:3").unwrap(), vec![2, 4, 7]);
+        assert_eq!(select("last").unwrap(), vec![7]);
+        assert_eq!(select("1,last").unwrap(), vec![0, 7]);
+    }
+
+    #[test]
+    fn exclusions_apply_before_the_other_terms() {
+        assert_eq!(
+            select("exclude-outlap,exclude-inlap").unwrap(),
+            (1..7).collect::<Vec<_>>()
+        );
+        assert_eq!(select("last,exclude-inlap").unwrap(), vec![6]);
+        assert_eq!(select("1-3,exclude-outlap").unwrap(), vec![1, 2]);
+        assert_eq!(select("best:3,exclude-inlap").unwrap(), vec![2, 4, 5]);
+    }
+
+    #[test]
+    fn invalid_selections() {
+        for selection in ["", "0", "3-1", "best:0", "first", "2-x"] {
+            assert!(
+                matches!(
+                    selection.parse::<LapSelection>(),
+                    Err(Error::InvalidArgument(_))
+                ),
+                "{}",
+                selection
+            );
+        }
+    }
+
+    fn catalog() -> ChannelCatalog {
+        let source = [
+            "Logger Temperature",
+            "P_BRK_FRONT",
+            "P_BRK_REAR",
+            "RPM",
+            "GPS Speed",
+            "GPS Heading",
+            "Temp, Oil",
+        ]
+        .iter()
+        .fold(MemorySource::new(), |source, name| {
+            source.with_channel(ChannelFamily::Regular, name, "", vec![0.0], vec![0.0])
+        });
+        ChannelCatalog::build(&source)
+    }
+
+    fn channels(selection: &str) -> Result<Vec<String>> {
+        let aliases = BTreeMap::from([
+            ("brake_front".to_string(), "P_BRK_FRONT".to_string()),
+            ("brakes".to_string(), "P_BRK_FRONT, P_BRK_REAR".to_string()),
+        ]);
+        let catalog = catalog();
+
+        Ok(ChannelSelection::parse(selection, &aliases)?
+            .select(&catalog)?
+            .into_iter()
+            .map(|entry| entry.name.clone())
+            .collect())
+    }
+
+    #[test]
+    fn names_globs_and_regexes() {
+        assert_eq!(
+            channels("rpm, logger temperature").unwrap(),
+            ["Logger Temperature", "RPM"]
+        );
+        assert_eq!(channels("gps *").unwrap(), ["GPS Speed", "GPS Heading"]);
+        assert_eq!(
+            channels("re:^p_brk").unwrap(),
+            ["P_BRK_FRONT", "P_BRK_REAR"]
+        );
+        assert_eq!(channels("").unwrap().len(), 7);
+    }
+
+    #[test]
+    fn commas_within_a_term_do_not_split_it() {
+        assert_eq!(channels("re:^.{1,3}$").unwrap(), ["RPM"]);
+        assert_eq!(channels(r"temp\, oil,RPM").unwrap(), ["RPM", "Temp, Oil"]);
+        assert_eq!(channels(r"temp\, *").unwrap(), ["Temp, Oil"]);
+        assert_eq!(channels(r"re:^temp\, oil$").unwrap(), ["Temp, Oil"]);
+        assert_eq!(
+            channels("re:^p_brk_(front|rear)$,gps {sp,head}*").unwrap(),
+            ["P_BRK_FRONT", "P_BRK_REAR", "GPS Speed", "GPS Heading"]
+        );
+    }
+
+    #[test]
+    fn aliases_expand_to_their_channels() {
+        assert_eq!(channels("Brake_Front").unwrap(), ["P_BRK_FRONT"]);
+        assert_eq!(
+            channels("brakes,RPM").unwrap(),
+            ["P_BRK_FRONT", "P_BRK_REAR", "RPM"]
+        );
+    }
+
+    #[test]
+    fn unknown_channels_suggest_close_matches() {
+        match channels("P_BRK_FRNT") {
+            Err(Error::UnknownChannel { name, suggestions }) => {
+                assert_eq!(name, "P_BRK_FRNT");
+                assert_eq!(suggestions.first().map(String::as_str), Some("P_BRK_FRONT"));
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert!(matches!(
+            channels("Oil *"),
+            Err(Error::UnknownChannel { suggestions, .. }) if suggestions.is_empty()
+        ));
+        assert!(matches!(channels("re:("), Err(Error::InvalidArgument(_))));
+    }
+}
@@ -0,0 +1,213 @@
+use super::{ChannelFamily, ChannelSamples, LapInfo, TelemetrySource};
+use crate::error::{Error, Result};
+use crate::units::{Conversion, UnitPreferences};
+use chrono::NaiveDateTime;
+use std::collections::HashMap;
+
+/// A session with its channels presented in the preferred units.
+pub struct ConvertedSource {
+    inner: Box<dyn TelemetrySource>,
+    conversions: HashMap<(ChannelFamily, usize), (String, Conversion)>,
+}
+
+impl ConvertedSource {
+    /// Fails if a channel cannot be converted into its overridden unit, or if
+    /// a required override names no channel of the session.
+    pub fn new(inner: Box<dyn TelemetrySource>, preferences: &UnitPreferences) -> Result<Self> {
+        let mut conversions = HashMap::new();
+        let mut names = Vec::new();
+
+        for family in ChannelFamily::ALL {
+            for index in 0..inner.channels_count(family) {
+                let name = inner.channel_name(family, index).unwrap_or_default();
+                let unit = inner.channel_unit(family, index).unwrap_or_default();
+                if let Some(conversion) = preferences.conversion(&name, &unit)? {
+                    conversions.insert((family, index), conversion);
+                }
+                names.push(name);
+            }
+        }
+
+        if let Some(unknown) = preferences
+            .required
+            .iter()
+            .find(|channel| !names.iter().any(|name| name.eq_ignore_ascii_case(channel)))
+        {
+            return Err(Error::unknown_channel(unknown));
+        }
+
+        Ok(Self { inner, conversions })
+    }
+
+    /// `data` of the channel at `index` in its presented unit.
+    fn convert(&self, family: ChannelFamily, index: usize, data: ChannelSamples) -> ChannelSamples {
+        match self.conversions.get(&(family, index)) {
+            Some((_, conversion)) => ChannelSamples::new(
+                data.timestamps().to_vec(),
+                data.samples()
+                    .iter()
+                    .map(|&value| conversion.apply(value))
+                    .collect(),
+            ),
+            None => data,
+        }
+    }
+}
+
+impl TelemetrySource for ConvertedSource {
+    fn championship(&self) -> Option<String> {
+        self.inner.championship()
+    }
+
+    fn track(&self) -> Option<String> {
+        self.inner.track()
+    }
+
+    fn venue_type(&self) -> Option<String> {
+        self.inner.venue_type()
+    }
+
+    fn vehicle(&self) -> Option<String> {
+        self.inner.vehicle()
+    }
+
+    fn racer(&self) -> Option<String> {
+        self.inner.racer()
+    }
+
+    fn datetime(&self) -> Option<NaiveDateTime> {
+        self.inner.datetime()
+    }
+
+    fn number_of_laps(&self) -> usize {
+        self.inner.number_of_laps()
+    }
+
+    fn lap_info(&self, lap_index: usize) -> Option<LapInfo> {
+        self.inner.lap_info(lap_index)
+    }
+
+    fn lap_time(&self, lap_index: usize) -> Option<f64> {
+        self.inner.lap_time(lap_index)
+    }
+
+    fn channels_count(&self, family: ChannelFamily) -> usize {
+        self.inner.channels_count(family)
+    }
+
+    fn channel_name(&self, family: ChannelFamily, index: usize) -> Option<String> {
+        self.inner.channel_name(family, index)
+    }
+
+    fn channel_unit(&self, family: ChannelFamily, index: usize) -> Option<String> {
+        match self.conversions.get(&(family, index)) {
+            Some((unit, _)) => Some(unit.clone()),
+            None => self.inner.channel_unit(family, index),
+        }
+    }
+
+    fn channel_samples(&self, family: ChannelFamily, index: usize) -> Option<ChannelSamples> {
+        let data = self.inner.channel_samples(family, index)?;
+        Some(self.convert(family, index, data))
+    }
+
+    fn channel_samples_count(&self, family: ChannelFamily, index: usize) -> usize {
+        self.inner.channel_samples_count(family, index)
+    }
+
+    fn lap_channel_samples(
+        &self,
+        family: ChannelFamily,
+        lap_index: usize,
+        index: usize,
+    ) -> Option<ChannelSamples> {
+        let data = self.inner.lap_channel_samples(family, lap_index, index)?;
+        Some(self.convert(family, index, data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::MemorySource;
+    use crate::units::UnitSystem;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn channels_are_converted_into_the_preferred_units() {
+        let source = MemorySource::new()
+            .with_lap(0.0, 2.0)
+            .with_channel(
+                ChannelFamily::Gps,
+                "GPS Speed",
+                "km/h",
+                vec![0.0, 1.0],
+                vec![0.0, 36.0],
+            )
+            .with_channel(
+                ChannelFamily::Regular,
+                "RPM",
+                "rpm",
+                vec![0.0],
+                vec![3000.0],
+            );
+        let preferences = UnitPreferences {
+            system: Some(UnitSystem::Si),
+            ..UnitPreferences::default()
+        };
+        let converted = ConvertedSource::new(Box::new(source), &preferences).unwrap();
+
+        assert_eq!(
+            converted.channel_unit(ChannelFamily::Gps, 0).as_deref(),
+            Some("m/s")
+        );
+        assert_eq!(
+            converted
+                .channel_samples(ChannelFamily::Gps, 0)
+                .unwrap()
+                .samples(),
+            &[0.0, 10.0]
+        );
+        assert_eq!(
+            converted
+                .lap_channel_samples(ChannelFamily::Gps, 0, 0)
+                .unwrap()
+                .samples(),
+            &[0.0, 10.0]
+        );
+        assert_eq!(
+            converted
+                .channel_samples(ChannelFamily::Regular, 0)
+                .unwrap()
+                .samples(),
+            &[3000.0]
+        );
+    }
+
+    #[test]
+    fn only_required_overrides_of_unknown_channels_are_rejected() {
+        let source = || {
+            MemorySource::new().with_channel(
+                ChannelFamily::Regular,
+                "Oil Temp",
+                "°C",
+                vec![0.0],
+                vec![100.0],
+            )
+        };
+        let mut preferences = UnitPreferences {
+            overrides: BTreeMap::from([
+                ("GPS Speed".to_string(), "mph".to_string()),
+                ("Oil Tmp".to_string(), "°F".to_string()),
+            ]),
+            ..UnitPreferences::default()
+        };
+        assert!(ConvertedSource::new(Box::new(source()), &preferences).is_ok());
+
+        preferences.required.insert("Oil Tmp".to_string());
+        assert!(matches!(
+            ConvertedSource::new(Box::new(source()), &preferences),
+            Err(Error::UnknownChannel { name, .. }) if name == "Oil Tmp"
+        ));
+    }
+}
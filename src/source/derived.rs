@@ -0,0 +1,452 @@
+use super::{ChannelFamily, ChannelSamples, LapInfo, TelemetrySource};
+use crate::export::distance::POSITION_CHANNEL_NAMES;
+use crate::geo::{ecef_to_enu, is_fix, Geodetic};
+use crate::units::Conversion;
+use chrono::NaiveDateTime;
+
+const VELOCITY_CHANNEL_NAMES: [&str; 3] = ["ECEF velocity_X", "ECEF velocity_Y", "ECEF velocity_Z"];
+
+struct DerivedChannel {
+    name: &'static str,
+    unit: &'static str,
+    data: ChannelSamples,
+}
+
+/// A session with GPS channels computed from the ECEF positions and
+/// velocities added after its own GPS channels.
+///
+/// From the positions: `GPS Latitude` and `GPS Longitude` (WGS84, deg),
+/// `GPS Altitude` (above the ellipsoid, m) and `GPS East`, `GPS North` and
+/// `GPS Up` (m from the first GPS fix). From the velocities, in the local
+/// frame of each position: `GPS Ground Speed` (km/h) and `GPS Heading` (deg
+/// clockwise from north). Samples from before the GPS module had a fix are
+/// left out. A channel the session already has keeps its own data.
+pub struct DerivedSource {
+    inner: Box<dyn TelemetrySource>,
+    derived: Vec<DerivedChannel>,
+}
+
+/// The three ECEF channels of a vector in `unit`, cut to the same length;
+/// `None` unless all three have samples.
+fn ecef_channels(
+    source: &dyn TelemetrySource,
+    names: [&str; 3],
+    unit: &str,
+) -> Option<(Vec<f64>, Vec<[f64; 3]>)> {
+    let family = ChannelFamily::GpsRaw;
+    let channels = names.map(|name| {
+        let index = (0..source.channels_count(family))
+            .find(|&index| source.channel_name(family, index).as_deref() == Some(name))?;
+        let conversion = Conversion::between(&source.channel_unit(family, index)?, unit)?;
+        let data = source.channel_samples(family, index)?;
+        Some((conversion, data))
+    });
+    let [Some(x), Some(y), Some(z)] = channels else {
+        return None;
+    };
+
+    let count = x.1.len().min(y.1.len()).min(z.1.len());
+    if count == 0 {
+        return None;
+    }
+    let vectors = (0..count)
+        .map(|i| [&x, &y, &z].map(|(conversion, data)| conversion.apply(data.samples()[i])))
+        .collect();
+
+    Some((x.1.timestamps()[..count].to_vec(), vectors))
+}
+
+impl DerivedSource {
+    pub fn new(inner: Box<dyn TelemetrySource>) -> Self {
+        let mut derived = Vec::new();
+        let mut add = |name, unit, timestamps: &[f64], samples| {
+            derived.push(DerivedChannel {
+                name,
+                unit,
+                data: ChannelSamples::new(timestamps.to_vec(), samples),
+            })
+        };
+
+        // Only the actual fixes, without the positions recorded before them.
+        let positions = ecef_channels(inner.as_ref(), POSITION_CHANNEL_NAMES, "m")
+            .map(|(timestamps, positions)| -> (Vec<f64>, Vec<[f64; 3]>) {
+                timestamps
+                    .into_iter()
+                    .zip(positions)
+                    .filter(|&(_, position)| is_fix(position))
+                    .unzip()
+            })
+            .filter(|(timestamps, _)| !timestamps.is_empty());
+        if let Some((timestamps, positions)) = &positions {
+            let origin_position = positions[0];
+            let geodetic: Vec<Geodetic> = positions
+                .iter()
+                .map(|&[x, y, z]| Geodetic::from_ecef(x, y, z))
+                .collect();
+            let [x, y, z] = origin_position;
+            let origin = Geodetic::from_ecef(x, y, z);
+            let enu: Vec<[f64; 3]> = positions
+                .iter()
+                .map(|position| {
+                    let displacement = [0, 1, 2].map(|axis| position[axis] - origin_position[axis]);
+                    ecef_to_enu(displacement, &origin)
+                })
+                .collect();
+
+            add(
+                "GPS Latitude",
+                "deg",
+                timestamps,
+                geodetic.iter().map(|point| point.latitude).collect(),
+            );
+            add(
+                "GPS Longitude",
+                "deg",
+                timestamps,
+                geodetic.iter().map(|point| point.longitude).collect(),
+            );
+            add(
+                "GPS Altitude",
+                "m",
+                timestamps,
+                geodetic.iter().map(|point| point.altitude).collect(),
+            );
+            for (axis, name) in ["GPS East", "GPS North", "GPS Up"].into_iter().enumerate() {
+                add(
+                    name,
+                    "m",
+                    timestamps,
+                    enu.iter().map(|vector| vector[axis]).collect(),
+                );
+            }
+        }
+
+        if let (Some((position_timestamps, positions)), Some((timestamps, velocities))) = (
+            &positions,
+            ecef_channels(inner.as_ref(), VELOCITY_CHANNEL_NAMES, "m/s"),
+        ) {
+            // In the local frame of the position of the same fix; velocities
+            // without a position at their time are left out.
+            let (timestamps, horizontal): (Vec<f64>, Vec<(f64, f64)>) = timestamps
+                .iter()
+                .zip(velocities)
+                .filter_map(|(&time, velocity)| {
+                    let at = position_timestamps.partition_point(|&t| t < time);
+                    let &[x, y, z] = positions
+                        .get(at)
+                        .filter(|_| position_timestamps[at] == time)?;
+                    let [east, north, _] = ecef_to_enu(velocity, &Geodetic::from_ecef(x, y, z));
+                    Some((time, (east, north)))
+                })
+                .unzip();
+
+            if !timestamps.is_empty() {
+                add(
+                    "GPS Ground Speed",
+                    "km/h",
+                    &timestamps,
+                    horizontal
+                        .iter()
+                        .map(|(east, north)| east.hypot(*north) * 3.6)
+                        .collect(),
+                );
+                add(
+                    "GPS Heading",
+                    "deg",
+                    &timestamps,
+                    horizontal
+                        .iter()
+                        .map(|(east, north)| east.atan2(*north).to_degrees().rem_euclid(360.0))
+                        .collect(),
+                );
+            }
+        }
+
+        let existing: Vec<String> = (0..inner.channels_count(ChannelFamily::Gps))
+            .filter_map(|index| inner.channel_name(ChannelFamily::Gps, index))
+            .collect();
+        derived.retain(|channel| !existing.iter().any(|name| name == channel.name));
+
+        Self { inner, derived }
+    }
+
+    /// The derived channel at `index` within `family`, if it is one.
+    fn derived(&self, family: ChannelFamily, index: usize) -> Option<&DerivedChannel> {
+        match family {
+            ChannelFamily::Gps => {
+                let offset = self.inner.channels_count(family);
+                index.checked_sub(offset).and_then(|i| self.derived.get(i))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl TelemetrySource for DerivedSource {
+    fn championship(&self) -> Option<String> {
+        self.inner.championship()
+    }
+
+    fn track(&self) -> Option<String> {
+        self.inner.track()
+    }
+
+    fn venue_type(&self) -> Option<String> {
+        self.inner.venue_type()
+    }
+
+    fn vehicle(&self) -> Option<String> {
+        self.inner.vehicle()
+    }
+
+    fn racer(&self) -> Option<String> {
+        self.inner.racer()
+    }
+
+    fn datetime(&self) -> Option<NaiveDateTime> {
+        self.inner.datetime()
+    }
+
+    fn number_of_laps(&self) -> usize {
+        self.inner.number_of_laps()
+    }
+
+    fn lap_info(&self, lap_index: usize) -> Option<LapInfo> {
+        self.inner.lap_info(lap_index)
+    }
+
+    fn lap_time(&self, lap_index: usize) -> Option<f64> {
+        self.inner.lap_time(lap_index)
+    }
+
+    fn channels_count(&self, family: ChannelFamily) -> usize {
+        match family {
+            ChannelFamily::Gps => self.inner.channels_count(family) + self.derived.len(),
+            _ => self.inner.channels_count(family),
+        }
+    }
+
+    fn channel_name(&self, family: ChannelFamily, index: usize) -> Option<String> {
+        match self.derived(family, index) {
+            Some(channel) => Some(channel.name.to_string()),
+            None => self.inner.channel_name(family, index),
+        }
+    }
+
+    fn channel_unit(&self, family: ChannelFamily, index: usize) -> Option<String> {
+        match self.derived(family, index) {
+            Some(channel) => Some(channel.unit.to_string()),
+            None => self.inner.channel_unit(family, index),
+        }
+    }
+
+    fn channel_samples(&self, family: ChannelFamily, index: usize) -> Option<ChannelSamples> {
+        match self.derived(family, index) {
+            Some(channel) => Some(channel.data.clone()),
+            None => self.inner.channel_samples(family, index),
+        }
+    }
+
+    fn channel_samples_count(&self, family: ChannelFamily, index: usize) -> usize {
+        match self.derived(family, index) {
+            Some(channel) => channel.data.len(),
+            None => self.inner.channel_samples_count(family, index),
+        }
+    }
+
+    fn lap_channel_samples(
+        &self,
+        family: ChannelFamily,
+        lap_index: usize,
+        index: usize,
+    ) -> Option<ChannelSamples> {
+        match self.derived(family, index) {
+            Some(channel) => {
+                let lap = self.lap_info(lap_index)?;
+                Some(channel.data.window(lap.start(), lap.end()))
+            }
+            None => self.inner.lap_channel_samples(family, lap_index, index),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::MemorySource;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-6,
+            "{} is not close to {}",
+            actual,
+            expected
+        );
+    }
+
+    // Heading north-east at 10 m/s in each direction, from Zandvoort.
+    fn session() -> MemorySource {
+        let origin = Geodetic {
+            latitude: 52.3888,
+            longitude: 4.5409,
+            altitude: 12.0,
+        };
+        let (sin_lat, cos_lat) = origin.latitude.to_radians().sin_cos();
+        let (sin_lon, cos_lon) = origin.longitude.to_radians().sin_cos();
+        let east = [-sin_lon, cos_lon, 0.0];
+        let north = [-sin_lat * cos_lon, -sin_lat * sin_lon, cos_lat];
+        let velocity: [f64; 3] = [0, 1, 2].map(|axis| 10.0 * (east[axis] + north[axis]));
+
+        let timestamps = vec![0.0, 1.0, 2.0];
+        let start = origin.to_ecef();
+        let mut source = MemorySource::new()
+            .with_lap(0.0, 3.0)
+            .with_channel(
+                ChannelFamily::Gps,
+                "GPS Speed",
+                "km/h",
+                timestamps.clone(),
+                vec![50.9; 3],
+            )
+            .with_channel(
+                ChannelFamily::Gps,
+                "GPS Heading",
+                "deg",
+                timestamps.clone(),
+                vec![45.0; 3],
+            );
+        for (axis, name) in POSITION_CHANNEL_NAMES.into_iter().enumerate() {
+            source = source.with_channel(
+                ChannelFamily::GpsRaw,
+                name,
+                "m",
+                timestamps.clone(),
+                timestamps
+                    .iter()
+                    .map(|t| start[axis] + t * velocity[axis])
+                    .collect(),
+            );
+        }
+        for (axis, name) in VELOCITY_CHANNEL_NAMES.into_iter().enumerate() {
+            source = source.with_channel(
+                ChannelFamily::GpsRaw,
+                name,
+                "m/s",
+                timestamps.clone(),
+                vec![velocity[axis]; 3],
+            );
+        }
+        source
+    }
+
+    fn channel(source: &DerivedSource, name: &str) -> Vec<f64> {
+        let index = (0..source.channels_count(ChannelFamily::Gps))
+            .find(|&index| source.channel_name(ChannelFamily::Gps, index).as_deref() == Some(name))
+            .unwrap_or_else(|| panic!("no channel {}", name));
+        source
+            .channel_samples(ChannelFamily::Gps, index)
+            .unwrap()
+            .samples()
+            .to_vec()
+    }
+
+    #[test]
+    fn channels_follow_the_native_gps_channels() {
+        let source = DerivedSource::new(Box::new(session()));
+
+        let names: Vec<String> = (0..source.channels_count(ChannelFamily::Gps))
+            .filter_map(|index| source.channel_name(ChannelFamily::Gps, index))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "GPS Speed",
+                "GPS Heading",
+                "GPS Latitude",
+                "GPS Longitude",
+                "GPS Altitude",
+                "GPS East",
+                "GPS North",
+                "GPS Up",
+                "GPS Ground Speed",
+            ]
+        );
+        assert_eq!(
+            source.channel_unit(ChannelFamily::Gps, 2).as_deref(),
+            Some("deg")
+        );
+        assert_eq!(
+            source
+                .lap_channel_samples(ChannelFamily::Gps, 0, 8)
+                .map(|data| data.len()),
+            Some(3)
+        );
+    }
+
+    #[test]
+    fn positions_and_velocities_in_local_terms() {
+        let source = DerivedSource::new(Box::new(session()));
+
+        assert_close(channel(&source, "GPS Latitude")[0], 52.3888);
+        assert_close(channel(&source, "GPS Longitude")[0], 4.5409);
+        assert_close(channel(&source, "GPS Altitude")[0], 12.0);
+        for axis in ["GPS East", "GPS North"] {
+            let values = channel(&source, axis);
+            assert_close(values[0], 0.0);
+            assert!((values[2] - 20.0).abs() < 1e-3, "{}: {:?}", axis, values);
+        }
+        assert_close(channel(&source, "GPS Ground Speed")[1], 200f64.sqrt() * 3.6);
+    }
+
+    #[test]
+    fn origin_is_the_first_fix_and_velocities_follow_positions_by_time() {
+        let mut source = MemorySource::new().with_lap(0.0, 3.0);
+        let fix = Geodetic {
+            latitude: 52.3888,
+            longitude: 4.5409,
+            altitude: 12.0,
+        }
+        .to_ecef();
+        // No fix at first, then a stationary car.
+        for (axis, name) in POSITION_CHANNEL_NAMES.into_iter().enumerate() {
+            source = source.with_channel(
+                ChannelFamily::GpsRaw,
+                name,
+                "m",
+                vec![0.0, 1.0, 2.0],
+                vec![0.0, fix[axis], fix[axis]],
+            );
+        }
+        // Velocities logged at other times than the positions, but for one.
+        for name in VELOCITY_CHANNEL_NAMES {
+            source = source.with_channel(
+                ChannelFamily::GpsRaw,
+                name,
+                "m/s",
+                vec![0.5, 2.0, 2.5],
+                vec![0.0; 3],
+            );
+        }
+        let source = DerivedSource::new(Box::new(source));
+
+        for name in ["GPS Latitude", "GPS Altitude", "GPS East", "GPS Up"] {
+            assert_eq!(channel(&source, name).len(), 2, "{}", name);
+        }
+        assert_close(channel(&source, "GPS Latitude")[0], 52.3888);
+        assert_close(channel(&source, "GPS East")[0], 0.0);
+        assert_close(channel(&source, "GPS Up")[1], 0.0);
+        let index = (0..source.channels_count(ChannelFamily::Gps))
+            .find(|&index| {
+                source.channel_name(ChannelFamily::Gps, index).as_deref()
+                    == Some("GPS Ground Speed")
+            })
+            .unwrap();
+        assert_eq!(
+            source
+                .channel_samples(ChannelFamily::Gps, index)
+                .unwrap()
+                .timestamps(),
+            &[2.0]
+        );
+    }
+}
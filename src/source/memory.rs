@@ -0,0 +1,203 @@
+use super::{ChannelFamily, ChannelSamples, LapInfo, TelemetrySource};
+use chrono::NaiveDateTime;
+
+struct MemoryChannel {
+    family: ChannelFamily,
+    name: String,
+    unit: String,
+    data: ChannelSamples,
+}
+
+/// Session held entirely in memory, e.g. synthetic sessions for tests.
+#[derive(Default)]
+pub struct MemorySource {
+    championship: Option<String>,
+    track: Option<String>,
+    venue_type: Option<String>,
+    vehicle: Option<String>,
+    racer: Option<String>,
+    datetime: Option<NaiveDateTime>,
+    laps: Vec<LapInfo>,
+    channels: Vec<MemoryChannel>,
+}
+
+impl MemorySource {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_championship(mut self, championship: &str) -> Self {
+        self.championship = Some(championship.to_string());
+        self
+    }
+
+    pub fn with_track(mut self, track: &str) -> Self {
+        self.track = Some(track.to_string());
+        self
+    }
+
+    pub fn with_venue_type(mut self, venue_type: &str) -> Self {
+        self.venue_type = Some(venue_type.to_string());
+        self
+    }
+
+    pub fn with_vehicle(mut self, vehicle: &str) -> Self {
+        self.vehicle = Some(vehicle.to_string());
+        self
+    }
+
+    pub fn with_racer(mut self, racer: &str) -> Self {
+        self.racer = Some(racer.to_string());
+        self
+    }
+
+    pub fn with_datetime(mut self, datetime: NaiveDateTime) -> Self {
+        self.datetime = Some(datetime);
+        self
+    }
+
+    /// Appends a lap starting at `start` seconds and lasting `time` seconds.
+    pub fn with_lap(mut self, start: f64, time: f64) -> Self {
+        self.laps.push(LapInfo::new(self.laps.len(), start, time));
+        self
+    }
+
+    /// Appends a channel to the given family; its index is its position within that family.
+    pub fn with_channel(
+        mut self,
+        family: ChannelFamily,
+        name: &str,
+        unit: &str,
+        timestamps: Vec<f64>,
+        samples: Vec<f64>,
+    ) -> Self {
+        self.channels.push(MemoryChannel {
+            family,
+            name: name.to_string(),
+            unit: unit.to_string(),
+            data: ChannelSamples::new(timestamps, samples),
+        });
+        self
+    }
+
+    fn channel(&self, family: ChannelFamily, index: usize) -> Option<&MemoryChannel> {
+        self.channels
+            .iter()
+            .filter(|channel| channel.family == family)
+            .nth(index)
+    }
+}
+
+impl TelemetrySource for MemorySource {
+    fn championship(&self) -> Option<String> {
+        self.championship.clone()
+    }
+
+    fn track(&self) -> Option<String> {
+        self.track.clone()
+    }
+
+    fn venue_type(&self) -> Option<String> {
+        self.venue_type.clone()
+    }
+
+    fn vehicle(&self) -> Option<String> {
+        self.vehicle.clone()
+    }
+
+    fn racer(&self) -> Option<String> {
+        self.racer.clone()
+    }
+
+    fn datetime(&self) -> Option<NaiveDateTime> {
+        self.datetime
+    }
+
+    fn number_of_laps(&self) -> usize {
+        self.laps.len()
+    }
+
+    fn lap_info(&self, lap_index: usize) -> Option<LapInfo> {
+        self.laps.get(lap_index).copied()
+    }
+
+    fn channels_count(&self, family: ChannelFamily) -> usize {
+        self.channels
+            .iter()
+            .filter(|channel| channel.family == family)
+            .count()
+    }
+
+    fn channel_name(&self, family: ChannelFamily, index: usize) -> Option<String> {
+        self.channel(family, index)
+            .map(|channel| channel.name.clone())
+    }
+
+    fn channel_unit(&self, family: ChannelFamily, index: usize) -> Option<String> {
+        self.channel(family, index)
+            .map(|channel| channel.unit.clone())
+    }
+
+    fn channel_samples(&self, family: ChannelFamily, index: usize) -> Option<ChannelSamples> {
+        self.channel(family, index)
+            .map(|channel| channel.data.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session() -> MemorySource {
+        MemorySource::new()
+            .with_lap(0.0, 10.0)
+            .with_lap(10.0, 5.0)
+            .with_channel(
+                ChannelFamily::Regular,
+                "RPM",
+                "rpm",
+                vec![0.0, 5.0, 10.0, 12.0],
+                vec![1000.0, 2000.0, 3000.0, 4000.0],
+            )
+            .with_channel(
+                ChannelFamily::GpsRaw,
+                "ECEF position_X",
+                "m",
+                vec![0.0, 10.0],
+                vec![1.0, 2.0],
+            )
+    }
+
+    #[test]
+    fn channels_are_indexed_per_family() {
+        let source = session();
+
+        assert_eq!(source.channels_count(ChannelFamily::Regular), 1);
+        assert_eq!(source.channels_count(ChannelFamily::Gps), 0);
+        assert_eq!(source.channels_count(ChannelFamily::GpsRaw), 1);
+        assert_eq!(
+            source.channel_name(ChannelFamily::GpsRaw, 0).as_deref(),
+            Some("ECEF position_X")
+        );
+        assert_eq!(source.channel_name(ChannelFamily::Gps, 0), None);
+    }
+
+    #[test]
+    fn lap_samples_are_sliced_on_lap_boundaries() {
+        let source = session();
+
+        let lap = source
+            .lap_channel_samples(ChannelFamily::Regular, 1, 0)
+            .unwrap();
+        assert_eq!(lap.timestamps(), &[10.0, 12.0]);
+        assert_eq!(lap.samples(), &[3000.0, 4000.0]);
+        assert_eq!(
+            source.lap_channel_samples_count(ChannelFamily::Regular, 0, 0),
+            2
+        );
+        assert_eq!(
+            source.lap_channel_samples(ChannelFamily::Regular, 2, 0),
+            None
+        );
+    }
+}
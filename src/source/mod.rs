@@ -0,0 +1,261 @@
+use crate::error::{Error, Result};
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use std::fmt;
+use std::path::Path;
+
+pub mod converted;
+pub mod derived;
+pub mod memory;
+#[cfg(feature = "native")]
+pub mod native;
+pub mod window;
+#[cfg(feature = "xdrk")]
+pub mod xdrk;
+pub mod xrz;
+
+pub use self::converted::ConvertedSource;
+pub use self::derived::DerivedSource;
+pub use self::memory::MemorySource;
+pub use self::window::{TimeWindow, WindowedSource};
+#[cfg(feature = "xdrk")]
+pub use self::xdrk::XdrkSource;
+
+#[cfg(not(any(feature = "xdrk", feature = "native")))]
+compile_error!("at least one of the `xdrk` and `native` features must be enabled");
+
+/// Implementation used to decode session files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// AiM's `libmatlabxrk` (Linux x86_64 only).
+    #[cfg(feature = "xdrk")]
+    Xdrk,
+    /// The pure Rust decoder.
+    #[cfg(feature = "native")]
+    Native,
+}
+
+impl Default for Backend {
+    /// Prefers AiM's library when it is compiled in.
+    fn default() -> Self {
+        #[cfg(feature = "xdrk")]
+        return Backend::Xdrk;
+        #[cfg(not(feature = "xdrk"))]
+        return Backend::Native;
+    }
+}
+
+impl std::str::FromStr for Backend {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            #[cfg(feature = "xdrk")]
+            "xdrk" => Ok(Backend::Xdrk),
+            #[cfg(feature = "native")]
+            "native" => Ok(Backend::Native),
+            _ => Err(Error::InvalidArgument(format!(
+                "unknown or disabled backend '{}'",
+                s
+            ))),
+        }
+    }
+}
+
+/// Loads a session file (raw or compressed) with the default backend.
+pub fn load(path: &Path) -> Result<Box<dyn TelemetrySource>> {
+    load_with(path, Backend::default())
+}
+
+/// Loads a session file with the given backend.
+pub fn load_with(path: &Path, backend: Backend) -> Result<Box<dyn TelemetrySource>> {
+    if !path.exists() {
+        return Err(Error::FileNotFound(path.to_path_buf()));
+    }
+
+    let loaded: std::result::Result<
+        Box<dyn TelemetrySource>,
+        Box<dyn std::error::Error + Send + Sync>,
+    > = match backend {
+        #[cfg(feature = "xdrk")]
+        Backend::Xdrk => XdrkSource::load(path).map(|source| Box::new(source) as _),
+        #[cfg(feature = "native")]
+        Backend::Native => native::load(path).map(|source| Box::new(source) as _),
+    };
+
+    loaded.map_err(|err| Error::Load {
+        path: path.to_path_buf(),
+        message: err.to_string(),
+    })
+}
+
+/// The three kinds of channels an AiM logger records.
+///
+/// Regular channels are the sensors wired to the logger, GPS channels are the
+/// ones derived by the library from the GPS module (speed, heading, ...) and
+/// GPS raw channels are the unprocessed GPS receiver output (ECEF positions
+/// and velocities).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ChannelFamily {
+    Regular,
+    Gps,
+    GpsRaw,
+}
+
+impl ChannelFamily {
+    pub const ALL: [ChannelFamily; 3] = [
+        ChannelFamily::Regular,
+        ChannelFamily::Gps,
+        ChannelFamily::GpsRaw,
+    ];
+}
+
+impl fmt::Display for ChannelFamily {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ChannelFamily::Regular => "regular",
+            ChannelFamily::Gps => "gps",
+            ChannelFamily::GpsRaw => "gps-raw",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Start and duration of a single lap.
+///
+/// The lap index starts at 0, the lap number (as used on the timing screen)
+/// starts at 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LapInfo {
+    index: usize,
+    start: f64,
+    time: f64,
+}
+
+impl LapInfo {
+    pub fn new(index: usize, start: f64, time: f64) -> Self {
+        Self { index, start, time }
+    }
+
+    pub fn number(&self) -> usize {
+        self.index + 1
+    }
+
+    pub fn start(&self) -> f64 {
+        self.start
+    }
+
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    pub fn end(&self) -> f64 {
+        self.start + self.time
+    }
+}
+
+/// Timestamps (in seconds) and the matching sample values of a channel.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChannelSamples {
+    timestamps: Vec<f64>,
+    samples: Vec<f64>,
+}
+
+impl ChannelSamples {
+    pub fn new(timestamps: Vec<f64>, samples: Vec<f64>) -> Self {
+        assert_eq!(
+            timestamps.len(),
+            samples.len(),
+            "number of timestamps not equivalent to number of samples"
+        );
+        Self {
+            timestamps,
+            samples,
+        }
+    }
+
+    pub fn timestamps(&self) -> &[f64] {
+        &self.timestamps
+    }
+
+    pub fn samples(&self) -> &[f64] {
+        &self.samples
+    }
+
+    pub fn len(&self) -> usize {
+        self.timestamps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.timestamps.is_empty()
+    }
+
+    /// Returns the samples with a timestamp in `[start, end)`.
+    pub fn window(&self, start: f64, end: f64) -> Self {
+        let from = self.timestamps.partition_point(|&t| t < start);
+        let to = self.timestamps.partition_point(|&t| t < end);
+        Self {
+            timestamps: self.timestamps[from..to].to_vec(),
+            samples: self.samples[from..to].to_vec(),
+        }
+    }
+}
+
+/// Read access to a recorded session, independent of where the data comes from.
+///
+/// Lookups that fail (unknown lap or channel, unreadable data) return `None`.
+pub trait TelemetrySource {
+    fn championship(&self) -> Option<String>;
+    fn track(&self) -> Option<String>;
+    fn venue_type(&self) -> Option<String>;
+    fn vehicle(&self) -> Option<String>;
+    fn racer(&self) -> Option<String>;
+    fn datetime(&self) -> Option<NaiveDateTime>;
+
+    fn number_of_laps(&self) -> usize;
+    fn lap_info(&self, lap_index: usize) -> Option<LapInfo>;
+
+    /// Time of the whole lap as recorded, even where the source presents only
+    /// part of it.
+    fn lap_time(&self, lap_index: usize) -> Option<f64> {
+        self.lap_info(lap_index).map(|lap| lap.time())
+    }
+
+    fn channels_count(&self, family: ChannelFamily) -> usize;
+    fn channel_name(&self, family: ChannelFamily, index: usize) -> Option<String>;
+    fn channel_unit(&self, family: ChannelFamily, index: usize) -> Option<String>;
+    fn channel_samples(&self, family: ChannelFamily, index: usize) -> Option<ChannelSamples>;
+
+    fn channel_samples_count(&self, family: ChannelFamily, index: usize) -> usize {
+        self.channel_samples(family, index)
+            .map(|data| data.len())
+            .unwrap_or(0)
+    }
+
+    /// Samples of a channel restricted to a single lap.
+    ///
+    /// By default this slices the full channel on the lap boundaries from
+    /// `lap_info`; backends with a native per-lap accessor override it.
+    fn lap_channel_samples(
+        &self,
+        family: ChannelFamily,
+        lap_index: usize,
+        index: usize,
+    ) -> Option<ChannelSamples> {
+        let lap = self.lap_info(lap_index)?;
+        let data = self.channel_samples(family, index)?;
+        Some(data.window(lap.start(), lap.end()))
+    }
+
+    fn lap_channel_samples_count(
+        &self,
+        family: ChannelFamily,
+        lap_index: usize,
+        index: usize,
+    ) -> usize {
+        self.lap_channel_samples(family, lap_index, index)
+            .map(|data| data.len())
+            .unwrap_or(0)
+    }
+}
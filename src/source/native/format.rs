@@ -0,0 +1,365 @@
+//! Low level layout of the XRK container.
+//!
+//! An XRK file is a stream of two kinds of records:
+//!
+//! - header blocks: `<h`, tag (4 bytes), payload length (u32), version (u8)
+//!   and `>`, followed by the payload and a trailer of `<`, tag, checksum
+//!   (u16) and `>`. They carry the session metadata, channel descriptors
+//!   (`CHS`), lap markers (`LAP`) and raw GPS fixes (`GPS`);
+//! - data messages: either a single sample, `(G`, timecode (i32), channel
+//!   index (u16), value and `)`, or a run of equally spaced samples, `(M`,
+//!   channel index (u16), count (u16), timecode (i32), values and `)`.
+//!
+//! All integers are little endian and timecodes are in milliseconds since the
+//! logger was switched on. Messages this decoder does not know are skipped by
+//! scanning for the next record start.
+
+use std::collections::HashMap;
+use std::fmt;
+
+const BLOCK_HEADER_LEN: usize = 12;
+const BLOCK_TRAILER_LEN: usize = 8;
+const GPS_FIX_LEN: usize = 56;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormatError {
+    pub offset: usize,
+    pub message: String,
+}
+
+impl FormatError {
+    fn new(offset: usize, message: impl Into<String>) -> Self {
+        Self {
+            offset,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (at byte {})", self.message, self.offset)
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleType {
+    Unsigned,
+    Signed,
+    Float,
+}
+
+/// Payload of a `CHS` block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChannelDescriptor {
+    pub index: u16,
+    pub name: String,
+    pub unit: String,
+    pub decimals: u8,
+    pub sample_type: SampleType,
+    pub sample_size: usize,
+    /// Interval between samples of an `(M` run, in microseconds.
+    pub sample_period: u32,
+}
+
+impl ChannelDescriptor {
+    fn parse(payload: &[u8], offset: usize) -> Result<Self, FormatError> {
+        if payload.len() < 73 {
+            return Err(FormatError::new(offset, "truncated channel descriptor"));
+        }
+
+        let short_name = text(&payload[24..32]);
+        let long_name = text(&payload[32..64]);
+        let sample_type = match payload[20] {
+            0 => SampleType::Unsigned,
+            1 => SampleType::Signed,
+            _ => SampleType::Float,
+        };
+
+        Ok(Self {
+            index: u16_at(payload, 0),
+            name: if long_name.is_empty() {
+                short_name
+            } else {
+                long_name
+            },
+            unit: unit_name(payload[12] & 0x7f).to_string(),
+            decimals: payload[13],
+            sample_type,
+            sample_size: payload[72] as usize,
+            sample_period: u32_at(payload, 64),
+        })
+    }
+
+    fn decode(&self, bytes: &[u8]) -> f64 {
+        let raw = match (self.sample_type, self.sample_size) {
+            (SampleType::Float, 4) => f32::from_le_bytes(bytes[..4].try_into().unwrap()) as f64,
+            (SampleType::Float, 8) => f64::from_le_bytes(bytes[..8].try_into().unwrap()),
+            (SampleType::Signed, 1) => bytes[0] as i8 as f64,
+            (SampleType::Signed, 2) => i16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            (SampleType::Signed, 4) => i32_at(bytes, 0) as f64,
+            (_, 1) => bytes[0] as f64,
+            (_, 2) => u16_at(bytes, 0) as f64,
+            (_, 4) => u32_at(bytes, 0) as f64,
+            _ => f64::NAN,
+        };
+
+        match self.sample_type {
+            SampleType::Float => raw,
+            _ => raw / 10f64.powi(self.decimals as i32),
+        }
+    }
+}
+
+/// Payload of a `LAP` block.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LapMarker {
+    pub index: u16,
+    pub duration: u32,
+    pub end: u32,
+}
+
+/// One record of a `GPS` block, positions in cm and velocities in cm/s.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GpsFix {
+    pub timecode: u32,
+    pub position: [i32; 3],
+    pub velocity: [i32; 3],
+}
+
+/// Everything decoded from a file, before timecodes are turned into seconds.
+#[derive(Debug, Default)]
+pub struct Decoded {
+    /// Text of the metadata blocks (`RCR`, `VEH`, `TRK`, ...) keyed by tag.
+    pub metadata: HashMap<String, String>,
+    pub channels: Vec<ChannelDescriptor>,
+    /// Timecodes and values per channel index.
+    pub samples: HashMap<u16, (Vec<i64>, Vec<f64>)>,
+    pub laps: Vec<LapMarker>,
+    pub gps: Vec<GpsFix>,
+    /// Position in `channels` of the descriptor of each channel index.
+    descriptors: HashMap<u16, usize>,
+}
+
+impl Decoded {
+    fn descriptor(&self, index: u16) -> Option<&ChannelDescriptor> {
+        self.descriptors
+            .get(&index)
+            .map(|&position| &self.channels[position])
+    }
+
+    fn push_descriptor(&mut self, descriptor: ChannelDescriptor) {
+        // The first descriptor of an index wins, as with a linear search.
+        self.descriptors
+            .entry(descriptor.index)
+            .or_insert(self.channels.len());
+        self.channels.push(descriptor);
+    }
+
+    fn push_sample(&mut self, index: u16, timecode: i64, value: f64) {
+        let (timecodes, values) = self.samples.entry(index).or_default();
+        timecodes.push(timecode);
+        values.push(value);
+    }
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Decoded, FormatError> {
+    if !bytes.starts_with(b"<h") {
+        return Err(FormatError::new(0, "not an XRK file"));
+    }
+
+    let mut decoded = Decoded::default();
+    let mut pos = 0;
+
+    while pos < bytes.len() {
+        let rest = &bytes[pos..];
+
+        if rest.starts_with(b"<h") {
+            pos += decode_block(&mut decoded, bytes, pos)?;
+        } else if rest.starts_with(b"(G") {
+            match decode_sample(&mut decoded, bytes, pos) {
+                Some(len) => pos += len,
+                None => pos = resync(bytes, pos + 1),
+            }
+        } else if rest.starts_with(b"(M") {
+            match decode_samples(&mut decoded, bytes, pos) {
+                Some(len) => pos += len,
+                None => pos = resync(bytes, pos + 1),
+            }
+        } else {
+            pos = resync(bytes, pos + 1);
+        }
+    }
+
+    for (timecodes, values) in decoded.samples.values_mut() {
+        sort_by_timecode(timecodes, values);
+    }
+    decoded.gps.sort_by_key(|fix| fix.timecode);
+    decoded.laps.sort_by_key(|lap| lap.index);
+
+    Ok(decoded)
+}
+
+/// Decodes the header block at `pos`, returning its total length.
+fn decode_block(decoded: &mut Decoded, bytes: &[u8], pos: usize) -> Result<usize, FormatError> {
+    let header = bytes
+        .get(pos..pos + BLOCK_HEADER_LEN)
+        .ok_or_else(|| FormatError::new(pos, "truncated block header"))?;
+    if header[11] != b'>' {
+        return Err(FormatError::new(pos, "malformed block header"));
+    }
+
+    let tag = text(&header[2..6]);
+    let len = u32_at(header, 6) as usize;
+    let start = pos + BLOCK_HEADER_LEN;
+    let payload = bytes
+        .get(start..start + len)
+        .ok_or_else(|| FormatError::new(pos, format!("truncated {} block", tag)))?;
+    let trailer = bytes
+        .get(start + len..start + len + BLOCK_TRAILER_LEN)
+        .ok_or_else(|| FormatError::new(pos, format!("missing {} block trailer", tag)))?;
+    if trailer[0] != b'<' || trailer[7] != b'>' || trailer[1..5] != header[2..6] {
+        return Err(FormatError::new(
+            start + len,
+            format!("malformed {} block trailer", tag),
+        ));
+    }
+
+    match tag.as_str() {
+        "CHS" => decoded.push_descriptor(ChannelDescriptor::parse(payload, start)?),
+        "LAP" => {
+            if payload.len() < 24 {
+                return Err(FormatError::new(start, "truncated lap marker"));
+            }
+            decoded.laps.push(LapMarker {
+                index: u16_at(payload, 2),
+                duration: u32_at(payload, 4),
+                end: u32_at(payload, 20),
+            });
+        }
+        "GPS" => {
+            for fix in payload.chunks_exact(GPS_FIX_LEN) {
+                decoded.gps.push(GpsFix {
+                    timecode: u32_at(fix, 0),
+                    position: [i32_at(fix, 16), i32_at(fix, 20), i32_at(fix, 24)],
+                    velocity: [i32_at(fix, 32), i32_at(fix, 36), i32_at(fix, 40)],
+                });
+            }
+        }
+        "RCR" | "VEH" | "TRK" | "CMP" | "VTY" | "TMD" | "TMT" => {
+            decoded.metadata.insert(tag, text(payload));
+        }
+        _ => {}
+    }
+
+    Ok(BLOCK_HEADER_LEN + len + BLOCK_TRAILER_LEN)
+}
+
+/// Decodes a `(G` message at `pos`, returning its length.
+fn decode_sample(decoded: &mut Decoded, bytes: &[u8], pos: usize) -> Option<usize> {
+    let header = bytes.get(pos..pos + 8)?;
+    let timecode = i32_at(header, 2) as i64;
+    let index = u16_at(header, 6);
+    let descriptor = decoded.descriptor(index)?;
+    let len = 8 + descriptor.sample_size + 1;
+
+    let message = bytes.get(pos..pos + len)?;
+    if message[len - 1] != b')' {
+        return None;
+    }
+
+    let value = descriptor.decode(&message[8..len - 1]);
+    decoded.push_sample(index, timecode, value);
+    Some(len)
+}
+
+/// Decodes a `(M` message at `pos`, returning its length.
+fn decode_samples(decoded: &mut Decoded, bytes: &[u8], pos: usize) -> Option<usize> {
+    let header = bytes.get(pos..pos + 10)?;
+    let index = u16_at(header, 2);
+    let count = u16_at(header, 4) as usize;
+    let timecode = i32_at(header, 6) as i64;
+    let descriptor = decoded.descriptor(index)?.clone();
+    let len = 10 + count * descriptor.sample_size + 1;
+
+    let message = bytes.get(pos..pos + len)?;
+    if message[len - 1] != b')' || descriptor.sample_size == 0 {
+        return None;
+    }
+
+    for (i, value) in message[10..len - 1]
+        .chunks_exact(descriptor.sample_size)
+        .enumerate()
+    {
+        let offset = (i as i64 * descriptor.sample_period as i64) / 1000;
+        decoded.push_sample(index, timecode + offset, descriptor.decode(value));
+    }
+    Some(len)
+}
+
+/// Finds the start of the next record at or after `pos`.
+fn resync(bytes: &[u8], pos: usize) -> usize {
+    (pos..bytes.len().saturating_sub(1))
+        .find(|&i| matches!(&bytes[i..i + 2], b"<h" | b"(G" | b"(M"))
+        .unwrap_or(bytes.len())
+}
+
+fn sort_by_timecode(timecodes: &mut Vec<i64>, values: &mut Vec<f64>) {
+    if timecodes.windows(2).all(|pair| pair[0] <= pair[1]) {
+        return;
+    }
+
+    let mut pairs: Vec<(i64, f64)> = timecodes
+        .iter()
+        .copied()
+        .zip(values.iter().copied())
+        .collect();
+    pairs.sort_by_key(|&(timecode, _)| timecode);
+    (*timecodes, *values) = pairs.into_iter().unzip();
+}
+
+fn text(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).trim().to_string()
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn i32_at(bytes: &[u8], offset: usize) -> i32 {
+    i32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+/// Unit codes used in channel descriptors.
+fn unit_name(code: u8) -> &'static str {
+    match code {
+        1 => "%",
+        3 => "g",
+        4 => "deg",
+        5 => "deg/s",
+        6 | 11 => "#",
+        9 => "Hz",
+        12 => "mm",
+        14 => "bar",
+        15 => "rpm",
+        16 => "km/h",
+        17 => "C",
+        18 => "ms",
+        19 => "Nm",
+        21 => "V",
+        22 => "l",
+        24 => "l/s",
+        27 => "A",
+        30 => "lambda",
+        31 => "gear",
+        43 => "kg",
+        _ => "",
+    }
+}
@@ -0,0 +1,328 @@
+//! Pure Rust decoder for XRK files, for platforms `libmatlabxrk` is not available on.
+//!
+//! Decoded sessions are held in memory as a [`MemorySource`]. Regular channels
+//! come from the channel descriptors and data messages, GPS raw channels
+//! (ECEF positions and velocities) from the GPS fixes, and the only GPS
+//! channel, `GPS Speed`, is computed from the ECEF velocity.
+
+use super::{xrz, ChannelFamily, MemorySource};
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use std::path::Path;
+
+mod format;
+
+pub use self::format::FormatError;
+
+/// Decodes the XRK (or compressed XRZ) file at `path`.
+pub fn load(path: &Path) -> Result<MemorySource, Box<dyn std::error::Error + Send + Sync>> {
+    let bytes = xrz::read(path)?;
+    Ok(decode(&bytes)?)
+}
+
+/// Decodes an XRK file already read into memory.
+pub fn decode(bytes: &[u8]) -> Result<MemorySource, FormatError> {
+    let decoded = format::decode(bytes)?;
+
+    // Timestamps are reported relative to the first recorded sample.
+    let origin = decoded
+        .samples
+        .values()
+        .filter_map(|(timecodes, _)| timecodes.first().copied())
+        .chain(decoded.gps.first().map(|fix| fix.timecode as i64))
+        .min()
+        .unwrap_or(0);
+    let seconds = |timecode: i64| (timecode - origin) as f64 / 1000.0;
+
+    let mut source = MemorySource::new();
+
+    let metadata = |tag: &str| decoded.metadata.get(tag).filter(|text| !text.is_empty());
+    if let Some(racer) = metadata("RCR") {
+        source = source.with_racer(racer);
+    }
+    if let Some(vehicle) = metadata("VEH") {
+        source = source.with_vehicle(vehicle);
+    }
+    if let Some(track) = metadata("TRK") {
+        source = source.with_track(track);
+    }
+    if let Some(championship) = metadata("CMP") {
+        source = source.with_championship(championship);
+    }
+    if let Some(venue_type) = metadata("VTY") {
+        source = source.with_venue_type(venue_type);
+    }
+    if let Some(datetime) = parse_datetime(metadata("TMD"), metadata("TMT")) {
+        source = source.with_datetime(datetime);
+    }
+
+    for lap in &decoded.laps {
+        let start = seconds(lap.end as i64 - lap.duration as i64);
+        source = source.with_lap(start, lap.duration as f64 / 1000.0);
+    }
+
+    for channel in &decoded.channels {
+        let (timecodes, values) = decoded
+            .samples
+            .get(&channel.index)
+            .cloned()
+            .unwrap_or_default();
+        let timestamps = timecodes.into_iter().map(seconds).collect();
+
+        source = source.with_channel(
+            ChannelFamily::Regular,
+            &channel.name,
+            &channel.unit,
+            timestamps,
+            values,
+        );
+    }
+
+    if !decoded.gps.is_empty() {
+        let timestamps: Vec<f64> = decoded
+            .gps
+            .iter()
+            .map(|fix| seconds(fix.timecode as i64))
+            .collect();
+
+        let speed = decoded
+            .gps
+            .iter()
+            .map(|fix| {
+                let [x, y, z] = fix.velocity.map(|v| v as f64 / 100.0);
+                (x * x + y * y + z * z).sqrt() * 3.6
+            })
+            .collect();
+        source = source.with_channel(
+            ChannelFamily::Gps,
+            "GPS Speed",
+            "km/h",
+            timestamps.clone(),
+            speed,
+        );
+
+        for (axis, name) in ["X", "Y", "Z"].iter().enumerate() {
+            let position = decoded
+                .gps
+                .iter()
+                .map(|fix| fix.position[axis] as f64 / 100.0)
+                .collect();
+            source = source.with_channel(
+                ChannelFamily::GpsRaw,
+                &format!("ECEF position_{}", name),
+                "m",
+                timestamps.clone(),
+                position,
+            );
+        }
+
+        for (axis, name) in ["X", "Y", "Z"].iter().enumerate() {
+            let velocity = decoded
+                .gps
+                .iter()
+                .map(|fix| fix.velocity[axis] as f64 / 100.0)
+                .collect();
+            source = source.with_channel(
+                ChannelFamily::GpsRaw,
+                &format!("ECEF velocity_{}", name),
+                "m/s",
+                timestamps.clone(),
+                velocity,
+            );
+        }
+    }
+
+    Ok(source)
+}
+
+/// Combines the `dd/mm/yyyy` date and `hh:mm:ss` time blocks.
+fn parse_datetime(date: Option<&String>, time: Option<&String>) -> Option<NaiveDateTime> {
+    let date = NaiveDate::parse_from_str(date?, "%d/%m/%Y").ok()?;
+    let time = time
+        .and_then(|time| NaiveTime::parse_from_str(time, "%H:%M:%S").ok())
+        .unwrap_or_default();
+    Some(date.and_time(time))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::TelemetrySource;
+
+    fn block(tag: &str, payload: &[u8]) -> Vec<u8> {
+        let mut tag_bytes = [0u8; 4];
+        tag_bytes[..tag.len()].copy_from_slice(tag.as_bytes());
+
+        let mut bytes = b"<h".to_vec();
+        bytes.extend_from_slice(&tag_bytes);
+        bytes.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        bytes.push(1);
+        bytes.push(b'>');
+        bytes.extend_from_slice(payload);
+        bytes.push(b'<');
+        bytes.extend_from_slice(&tag_bytes);
+        bytes.extend_from_slice(&[0, 0]);
+        bytes.push(b'>');
+        bytes
+    }
+
+    fn text_block(tag: &str, text: &str) -> Vec<u8> {
+        let mut payload = text.as_bytes().to_vec();
+        payload.push(0);
+        block(tag, &payload)
+    }
+
+    fn channel(
+        index: u16,
+        name: &str,
+        unit: u8,
+        sample_type: u8,
+        size: u8,
+        period: u32,
+    ) -> Vec<u8> {
+        let mut payload = vec![0u8; 112];
+        payload[0..2].copy_from_slice(&index.to_le_bytes());
+        payload[12] = unit;
+        payload[20] = sample_type;
+        payload[32..32 + name.len()].copy_from_slice(name.as_bytes());
+        payload[64..68].copy_from_slice(&period.to_le_bytes());
+        payload[72] = size;
+        block("CHS", &payload)
+    }
+
+    fn lap(index: u16, duration: u32, end: u32) -> Vec<u8> {
+        let mut payload = vec![0u8; 24];
+        payload[2..4].copy_from_slice(&index.to_le_bytes());
+        payload[4..8].copy_from_slice(&duration.to_le_bytes());
+        payload[20..24].copy_from_slice(&end.to_le_bytes());
+        block("LAP", &payload)
+    }
+
+    fn gps(fixes: &[(u32, [i32; 3], [i32; 3])]) -> Vec<u8> {
+        let mut payload = Vec::new();
+        for (timecode, position, velocity) in fixes {
+            let mut fix = vec![0u8; 56];
+            fix[0..4].copy_from_slice(&timecode.to_le_bytes());
+            for axis in 0..3 {
+                let at = 16 + axis * 4;
+                fix[at..at + 4].copy_from_slice(&position[axis].to_le_bytes());
+                let at = 32 + axis * 4;
+                fix[at..at + 4].copy_from_slice(&velocity[axis].to_le_bytes());
+            }
+            payload.extend(fix);
+        }
+        block("GPS", &payload)
+    }
+
+    fn sample(timecode: i32, index: u16, value: &[u8]) -> Vec<u8> {
+        let mut bytes = b"(G".to_vec();
+        bytes.extend_from_slice(&timecode.to_le_bytes());
+        bytes.extend_from_slice(&index.to_le_bytes());
+        bytes.extend_from_slice(value);
+        bytes.push(b')');
+        bytes
+    }
+
+    fn samples(index: u16, timecode: i32, values: &[u8], count: u16) -> Vec<u8> {
+        let mut bytes = b"(M".to_vec();
+        bytes.extend_from_slice(&index.to_le_bytes());
+        bytes.extend_from_slice(&count.to_le_bytes());
+        bytes.extend_from_slice(&timecode.to_le_bytes());
+        bytes.extend_from_slice(values);
+        bytes.push(b')');
+        bytes
+    }
+
+    fn file() -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend(text_block("RCR", "Driver"));
+        bytes.extend(text_block("VEH", "Clio"));
+        bytes.extend(text_block("TRK", "Zandvoort"));
+        bytes.extend(text_block("TMD", "01/05/2024"));
+        bytes.extend(text_block("TMT", "14:30:00"));
+        bytes.extend(channel(0, "RPM", 15, 0, 2, 0));
+        bytes.extend(channel(1, "Water Temp", 17, 2, 4, 100_000));
+        bytes.extend(sample(1000, 0, &3000u16.to_le_bytes()));
+        bytes.extend(b"(x???)");
+        bytes.extend(sample(1500, 0, &3500u16.to_le_bytes()));
+        let temps: Vec<u8> = [80.0f32, 80.5, 81.0]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        bytes.extend(samples(1, 1000, &temps, 3));
+        bytes.extend(gps(&[
+            (1000, [392_000_000, 30_000_000, 502_000_000], [1000, 0, 0]),
+            (1100, [392_000_100, 30_000_000, 502_000_000], [0, 1000, 0]),
+        ]));
+        bytes.extend(lap(0, 400, 1400));
+        bytes
+    }
+
+    #[test]
+    fn decodes_metadata_laps_and_channels() {
+        let source = decode(&file()).unwrap();
+
+        assert_eq!(source.racer().as_deref(), Some("Driver"));
+        assert_eq!(source.track().as_deref(), Some("Zandvoort"));
+        assert_eq!(
+            source.datetime().map(|datetime| datetime.to_string()),
+            Some("2024-05-01 14:30:00".to_string())
+        );
+
+        assert_eq!(source.number_of_laps(), 1);
+        let lap = source.lap_info(0).unwrap();
+        assert_eq!((lap.start(), lap.time()), (0.0, 0.4));
+
+        assert_eq!(source.channels_count(ChannelFamily::Regular), 2);
+        let rpm = source.channel_samples(ChannelFamily::Regular, 0).unwrap();
+        assert_eq!(rpm.timestamps(), &[0.0, 0.5]);
+        assert_eq!(rpm.samples(), &[3000.0, 3500.0]);
+        assert_eq!(
+            source.channel_unit(ChannelFamily::Regular, 0).as_deref(),
+            Some("rpm")
+        );
+
+        let temp = source.channel_samples(ChannelFamily::Regular, 1).unwrap();
+        assert_eq!(temp.timestamps(), &[0.0, 0.1, 0.2]);
+        assert_eq!(temp.samples(), &[80.0, 80.5, 81.0]);
+    }
+
+    #[test]
+    fn decodes_gps_fixes_into_gps_channels() {
+        let source = decode(&file()).unwrap();
+
+        assert_eq!(
+            source.channel_name(ChannelFamily::GpsRaw, 0).as_deref(),
+            Some("ECEF position_X")
+        );
+        let x = source.channel_samples(ChannelFamily::GpsRaw, 0).unwrap();
+        assert_eq!(x.timestamps(), &[0.0, 0.1]);
+        assert_eq!(x.samples(), &[3_920_000.0, 3_920_001.0]);
+
+        let speed = source.channel_samples(ChannelFamily::Gps, 0).unwrap();
+        assert_eq!(speed.samples(), &[36.0, 36.0]);
+    }
+
+    #[test]
+    fn loads_compressed_files() {
+        use flate2::write::ZlibEncoder;
+        use std::io::Write;
+
+        let mut xrz = tempfile::Builder::new().suffix(".xrz").tempfile().unwrap();
+        let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&file()).unwrap();
+        xrz.write_all(&encoder.finish().unwrap()).unwrap();
+
+        let source = load(xrz.path()).unwrap();
+        assert_eq!(source.racer().as_deref(), Some("Driver"));
+        assert_eq!(source.channels_count(ChannelFamily::Regular), 2);
+    }
+
+    #[test]
+    fn rejects_files_without_header_blocks() {
+        assert!(decode(b"PK\x03\x04").is_err());
+
+        let mut truncated = file();
+        truncated.truncate(20);
+        assert!(decode(&truncated).is_err());
+    }
+}
@@ -0,0 +1,274 @@
+use super::{ChannelFamily, ChannelSamples, LapInfo, TelemetrySource};
+use crate::error::{Error, Result};
+use chrono::NaiveDateTime;
+
+/// A time range of a session, in seconds from the start of the recording.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeWindow {
+    from: f64,
+    to: f64,
+}
+
+impl TimeWindow {
+    /// The range `[from, to)`; open-ended on a missing side.
+    pub fn new(from: Option<f64>, to: Option<f64>) -> Result<Self> {
+        let from = from.unwrap_or(f64::NEG_INFINITY);
+        let to = to.unwrap_or(f64::INFINITY);
+        if from >= to {
+            return Err(Error::InvalidArgument(format!(
+                "the time window ends ({}) before it starts ({})",
+                to, from
+            )));
+        }
+        Ok(Self { from, to })
+    }
+
+    pub fn from(&self) -> f64 {
+        self.from
+    }
+
+    pub fn to(&self) -> f64 {
+        self.to
+    }
+
+    fn clip(&self, lap: LapInfo) -> LapInfo {
+        let start = lap.start().clamp(self.from, self.to);
+        let end = lap.end().clamp(self.from, self.to);
+        LapInfo::new(lap.number() - 1, start, end - start)
+    }
+}
+
+/// Parses a time given in seconds (`83.5`) or as `mm:ss.sss` (`1:23.500`).
+pub fn parse_time(s: &str) -> Result<f64> {
+    let invalid = || {
+        Error::InvalidArgument(format!(
+            "invalid time '{}' (expected seconds or mm:ss.sss)",
+            s
+        ))
+    };
+
+    let seconds = match s.split_once(':') {
+        Some((minutes, seconds)) => {
+            let minutes = minutes.parse::<u64>().map_err(|_| invalid())?;
+            let seconds = seconds.parse::<f64>().map_err(|_| invalid())?;
+            if !(0.0..60.0).contains(&seconds) {
+                return Err(invalid());
+            }
+            minutes as f64 * 60.0 + seconds
+        }
+        None => s.parse::<f64>().map_err(|_| invalid())?,
+    };
+
+    if seconds.is_finite() && seconds >= 0.0 {
+        Ok(seconds)
+    } else {
+        Err(invalid())
+    }
+}
+
+/// A session restricted to a time window: every channel is sliced to the
+/// window and laps are clipped to it, keeping their numbers and lap times.
+///
+/// A session without laps gets the window (within the recorded data) as its
+/// single lap.
+pub struct WindowedSource {
+    inner: Box<dyn TelemetrySource>,
+    window: TimeWindow,
+    pseudo_lap: Option<LapInfo>,
+}
+
+impl WindowedSource {
+    pub fn new(inner: Box<dyn TelemetrySource>, window: TimeWindow) -> Self {
+        let pseudo_lap = match inner.number_of_laps() {
+            0 => data_range(inner.as_ref()).map(|(first, last)| {
+                // Up to just past the last sample, as laps end before their end time.
+                let start = window.from.max(first);
+                let end = window.to.min(last.next_up());
+                LapInfo::new(0, start, (end - start).max(0.0))
+            }),
+            _ => None,
+        };
+
+        Self {
+            inner,
+            window,
+            pseudo_lap,
+        }
+    }
+
+    pub fn window(&self) -> TimeWindow {
+        self.window
+    }
+}
+
+/// The first and last timestamp of any channel of the session.
+fn data_range(source: &dyn TelemetrySource) -> Option<(f64, f64)> {
+    ChannelFamily::ALL
+        .into_iter()
+        .flat_map(|family| (0..source.channels_count(family)).map(move |index| (family, index)))
+        .filter_map(|(family, index)| {
+            let data = source.channel_samples(family, index)?;
+            Some((*data.timestamps().first()?, *data.timestamps().last()?))
+        })
+        .reduce(|(first, last), (start, end)| (first.min(start), last.max(end)))
+}
+
+impl TelemetrySource for WindowedSource {
+    fn championship(&self) -> Option<String> {
+        self.inner.championship()
+    }
+
+    fn track(&self) -> Option<String> {
+        self.inner.track()
+    }
+
+    fn venue_type(&self) -> Option<String> {
+        self.inner.venue_type()
+    }
+
+    fn vehicle(&self) -> Option<String> {
+        self.inner.vehicle()
+    }
+
+    fn racer(&self) -> Option<String> {
+        self.inner.racer()
+    }
+
+    fn datetime(&self) -> Option<NaiveDateTime> {
+        self.inner.datetime()
+    }
+
+    fn number_of_laps(&self) -> usize {
+        match self.pseudo_lap {
+            Some(_) => 1,
+            None => self.inner.number_of_laps(),
+        }
+    }
+
+    fn lap_info(&self, lap_index: usize) -> Option<LapInfo> {
+        match self.pseudo_lap {
+            Some(lap) => (lap_index == 0).then_some(lap),
+            None => self
+                .inner
+                .lap_info(lap_index)
+                .map(|lap| self.window.clip(lap)),
+        }
+    }
+
+    fn lap_time(&self, lap_index: usize) -> Option<f64> {
+        match self.pseudo_lap {
+            Some(lap) => (lap_index == 0).then_some(lap.time()),
+            None => self.inner.lap_time(lap_index),
+        }
+    }
+
+    fn channels_count(&self, family: ChannelFamily) -> usize {
+        self.inner.channels_count(family)
+    }
+
+    fn channel_name(&self, family: ChannelFamily, index: usize) -> Option<String> {
+        self.inner.channel_name(family, index)
+    }
+
+    fn channel_unit(&self, family: ChannelFamily, index: usize) -> Option<String> {
+        self.inner.channel_unit(family, index)
+    }
+
+    fn channel_samples(&self, family: ChannelFamily, index: usize) -> Option<ChannelSamples> {
+        self.inner
+            .channel_samples(family, index)
+            .map(|data| data.window(self.window.from, self.window.to))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::selection::LapSelection;
+    use crate::source::MemorySource;
+
+    #[test]
+    fn times_in_seconds_or_minutes() {
+        assert_eq!(parse_time("83.5").unwrap(), 83.5);
+        assert_eq!(parse_time("1:23.500").unwrap(), 83.5);
+        assert_eq!(parse_time("12:05").unwrap(), 725.0);
+        for invalid in ["", "-1", "1:60", "a:10", "1:-5", "inf"] {
+            assert!(parse_time(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn channels_and_laps_are_sliced_to_the_window() {
+        let timestamps: Vec<f64> = (0..10).map(|i| i as f64).collect();
+        let source = MemorySource::new()
+            .with_lap(0.0, 4.0)
+            .with_lap(4.0, 4.0)
+            .with_lap(8.0, 2.0)
+            .with_channel(
+                ChannelFamily::Regular,
+                "RPM",
+                "rpm",
+                timestamps.clone(),
+                timestamps.clone(),
+            );
+        let windowed = WindowedSource::new(
+            Box::new(source),
+            TimeWindow::new(Some(2.5), Some(6.0)).unwrap(),
+        );
+
+        let data = windowed.channel_samples(ChannelFamily::Regular, 0).unwrap();
+        assert_eq!(data.timestamps(), &[3.0, 4.0, 5.0]);
+        assert_eq!(windowed.lap_info(0), Some(LapInfo::new(0, 2.5, 1.5)));
+        assert_eq!(windowed.lap_info(2).map(|lap| lap.time()), Some(0.0));
+        assert_eq!(windowed.lap_time(0), Some(4.0));
+        assert_eq!(
+            windowed
+                .lap_channel_samples(ChannelFamily::Regular, 1, 0)
+                .unwrap()
+                .samples(),
+            &[4.0, 5.0]
+        );
+        assert!(TimeWindow::new(Some(6.0), Some(2.5)).is_err());
+    }
+
+    #[test]
+    fn best_laps_are_ranked_on_their_whole_time() {
+        let source = MemorySource::new()
+            .with_lap(0.0, 5.0)
+            .with_lap(5.0, 3.0)
+            .with_lap(8.0, 4.0)
+            .with_lap(12.0, 2.0);
+        let windowed = WindowedSource::new(
+            Box::new(source),
+            TimeWindow::new(Some(4.0), Some(9.0)).unwrap(),
+        );
+
+        let select = |laps: &str| laps.parse::<LapSelection>().unwrap().select(&windowed);
+        assert_eq!(select("best").unwrap(), vec![1]);
+        assert_eq!(select("best:3").unwrap(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn a_session_without_laps_has_the_window_as_its_lap() {
+        let timestamps: Vec<f64> = (0..10).map(|i| i as f64).collect();
+        let source = MemorySource::new().with_channel(
+            ChannelFamily::Regular,
+            "RPM",
+            "rpm",
+            timestamps.clone(),
+            timestamps.clone(),
+        );
+        let windowed =
+            WindowedSource::new(Box::new(source), TimeWindow::new(Some(2.5), None).unwrap());
+
+        assert_eq!(windowed.number_of_laps(), 1);
+        assert_eq!(windowed.lap_info(0).map(|lap| lap.start()), Some(2.5));
+        assert_eq!(
+            windowed
+                .lap_channel_samples(ChannelFamily::Regular, 0, 0)
+                .unwrap()
+                .samples(),
+            &[3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0]
+        );
+    }
+}
@@ -0,0 +1,163 @@
+//! The environment `libmatlabxrk` expects while opening a file.
+//!
+//! The library reads `user/profiles/units.xml` relative to the working
+//! directory and complains on stderr (through libxml) when it is missing, and
+//! it reports every successful load on stdout. Loading therefore happens in a
+//! scratch working directory holding a units profile, with both streams
+//! redirected to a temporary file.
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+const UNITS_PROFILE: &str = "<?xml version=\"1.0\"?>\n<XML/>\n";
+
+// The working directory and the standard streams are process wide.
+static ENVIRONMENT: Mutex<()> = Mutex::new(());
+
+/// Runs `f` in the library's environment, returning its result and whatever
+/// the library printed in the meantime.
+pub fn with_library_environment<T>(f: impl FnOnce() -> T) -> io::Result<(T, String)> {
+    let _lock = ENVIRONMENT.lock().unwrap_or_else(|err| err.into_inner());
+
+    let scratch = tempfile::Builder::new().prefix("xrk-cli-").tempdir()?;
+    let profiles = scratch.path().join("user").join("profiles");
+    fs::create_dir_all(&profiles)?;
+    fs::write(profiles.join("units.xml"), UNITS_PROFILE)?;
+
+    let _cwd = WorkingDirectory::change_to(scratch.path().to_path_buf())?;
+    let capture = Capture::start()?;
+    let result = f();
+    let output = capture.finish()?;
+
+    Ok((result, output))
+}
+
+/// Restores the previous working directory when dropped.
+struct WorkingDirectory {
+    previous: PathBuf,
+}
+
+impl WorkingDirectory {
+    fn change_to(dir: PathBuf) -> io::Result<Self> {
+        let previous = env::current_dir()?;
+        env::set_current_dir(dir)?;
+        Ok(Self { previous })
+    }
+}
+
+impl Drop for WorkingDirectory {
+    fn drop(&mut self) {
+        let _ = env::set_current_dir(&self.previous);
+    }
+}
+
+/// Redirects stdout and stderr to a temporary file until finished or dropped.
+struct Capture {
+    file: File,
+    #[cfg(unix)]
+    saved: Vec<(libc::c_int, libc::c_int)>,
+}
+
+impl Capture {
+    #[cfg(unix)]
+    fn start() -> io::Result<Self> {
+        use std::os::unix::io::AsRawFd;
+
+        flush_all();
+        let file = tempfile::tempfile()?;
+        let mut capture = Self {
+            file,
+            saved: Vec::new(),
+        };
+
+        for fd in [libc::STDOUT_FILENO, libc::STDERR_FILENO] {
+            // SAFETY: plain descriptor juggling, the duplicates are closed in `restore`.
+            let saved = unsafe { libc::dup(fd) };
+            if saved < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            if unsafe { libc::dup2(capture.file.as_raw_fd(), fd) } < 0 {
+                let err = io::Error::last_os_error();
+                // SAFETY: `saved` is not tracked by `restore` yet, so it is closed here.
+                unsafe { libc::close(saved) };
+                return Err(err);
+            }
+            capture.saved.push((fd, saved));
+        }
+
+        Ok(capture)
+    }
+
+    #[cfg(not(unix))]
+    fn start() -> io::Result<Self> {
+        Ok(Self {
+            file: tempfile::tempfile()?,
+        })
+    }
+
+    fn finish(mut self) -> io::Result<String> {
+        self.restore();
+
+        let mut output = String::new();
+        self.file.rewind()?;
+        self.file.read_to_string(&mut output)?;
+        Ok(output)
+    }
+
+    fn restore(&mut self) {
+        #[cfg(unix)]
+        {
+            flush_all();
+            for (fd, saved) in self.saved.drain(..) {
+                // SAFETY: `saved` was obtained from `dup` in `start`.
+                unsafe {
+                    libc::dup2(saved, fd);
+                    libc::close(saved);
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Capture {
+    fn drop(&mut self) {
+        self.restore();
+    }
+}
+
+/// Flushes both Rust's and C's buffered standard streams.
+#[cfg(unix)]
+fn flush_all() {
+    let _ = io::stdout().flush();
+    let _ = io::stderr().flush();
+    // SAFETY: flushing all C streams is always allowed.
+    unsafe {
+        libc::fflush(std::ptr::null_mut());
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn runs_in_scratch_directory_with_units_profile_and_captures_output() {
+        let cwd = env::current_dir().unwrap();
+
+        let (profile, output) = with_library_environment(|| {
+            let message = b"File loaded successfully!\n";
+            // SAFETY: writing a valid buffer to the (redirected) stdout descriptor.
+            unsafe { libc::write(libc::STDOUT_FILENO, message.as_ptr().cast(), message.len()) };
+            fs::read_to_string(Path::new("user/profiles/units.xml")).unwrap()
+        })
+        .unwrap();
+
+        assert_eq!(profile, UNITS_PROFILE);
+        assert_eq!(output, "File loaded successfully!\n");
+        assert_eq!(env::current_dir().unwrap(), cwd);
+    }
+}
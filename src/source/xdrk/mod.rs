@@ -0,0 +1,174 @@
+use super::xrz;
+use super::{ChannelFamily, ChannelSamples, LapInfo, TelemetrySource};
+use chrono::NaiveDateTime;
+use std::io;
+use std::path::{self, Path};
+use std::sync::Arc;
+use tempfile::NamedTempFile;
+
+mod environment;
+
+/// Session backed by AiM's `libmatlabxrk` through the `xdrk` crate.
+pub struct XdrkSource {
+    run: Arc<::xdrk::Run>,
+    // The library only reads raw XRK files, so compressed sessions are
+    // decompressed to a temporary file that lives as long as the run.
+    _decompressed: Option<NamedTempFile>,
+    library_output: String,
+}
+
+impl XdrkSource {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let decompressed = match xrz::detect_file(path)? {
+            Some(_) => {
+                let mut decompressed = tempfile::Builder::new()
+                    .prefix("xrk-cli-")
+                    .suffix(".xrk")
+                    .tempfile()?;
+                io::copy(&mut xrz::open(path)?, decompressed.as_file_mut())?;
+                Some(decompressed)
+            }
+            None => None,
+        };
+
+        // The library is loaded from a scratch working directory, so relative
+        // paths have to be resolved first.
+        let path = match &decompressed {
+            Some(decompressed) => decompressed.path().to_path_buf(),
+            None => path::absolute(path)?,
+        };
+
+        let (run, library_output) =
+            environment::with_library_environment(|| ::xdrk::Run::load(&path))?;
+        let run = run.map_err(|err| match library_output.trim() {
+            "" => err.to_string(),
+            output => format!("{} ({})", err, output),
+        })?;
+
+        Ok(Self {
+            run,
+            _decompressed: decompressed,
+            library_output,
+        })
+    }
+
+    /// Whatever `libmatlabxrk` printed while loading the file.
+    pub fn library_output(&self) -> &str {
+        &self.library_output
+    }
+}
+
+fn into_samples(data: ::xdrk::ChannelData) -> ChannelSamples {
+    ChannelSamples::new(data.timestamps().clone(), data.samples().clone())
+}
+
+impl TelemetrySource for XdrkSource {
+    fn championship(&self) -> Option<String> {
+        self.run.championship().ok()
+    }
+
+    fn track(&self) -> Option<String> {
+        self.run.track().ok()
+    }
+
+    fn venue_type(&self) -> Option<String> {
+        self.run.venue_type().ok()
+    }
+
+    fn vehicle(&self) -> Option<String> {
+        self.run.vehicle().ok()
+    }
+
+    fn racer(&self) -> Option<String> {
+        self.run.racer().ok()
+    }
+
+    fn datetime(&self) -> Option<NaiveDateTime> {
+        self.run.datetime().ok()
+    }
+
+    fn number_of_laps(&self) -> usize {
+        self.run.number_of_laps()
+    }
+
+    fn lap_info(&self, lap_index: usize) -> Option<LapInfo> {
+        self.run
+            .lap_info(lap_index)
+            .ok()
+            .map(|info| LapInfo::new(info.idx(), info.start(), info.time()))
+    }
+
+    fn channels_count(&self, family: ChannelFamily) -> usize {
+        match family {
+            ChannelFamily::Regular => self.run.channels_count(),
+            ChannelFamily::Gps => self.run.gps_channels_count(),
+            ChannelFamily::GpsRaw => self.run.gps_raw_channels_count(),
+        }
+    }
+
+    fn channel_name(&self, family: ChannelFamily, index: usize) -> Option<String> {
+        match family {
+            ChannelFamily::Regular => self.run.channel_name(index),
+            ChannelFamily::Gps => self.run.gps_channel_name(index),
+            ChannelFamily::GpsRaw => self.run.gps_raw_channel_name(index),
+        }
+        .ok()
+    }
+
+    fn channel_unit(&self, family: ChannelFamily, index: usize) -> Option<String> {
+        match family {
+            ChannelFamily::Regular => self.run.channel_unit(index),
+            ChannelFamily::Gps => self.run.gps_channel_unit(index),
+            ChannelFamily::GpsRaw => self.run.gps_raw_channel_unit(index),
+        }
+        .ok()
+    }
+
+    fn channel_samples(&self, family: ChannelFamily, index: usize) -> Option<ChannelSamples> {
+        match family {
+            ChannelFamily::Regular => self.run.channel_samples(index),
+            ChannelFamily::Gps => self.run.gps_channel_samples(index),
+            ChannelFamily::GpsRaw => self.run.gps_raw_channel_samples(index),
+        }
+        .ok()
+        .map(into_samples)
+    }
+
+    fn channel_samples_count(&self, family: ChannelFamily, index: usize) -> usize {
+        match family {
+            ChannelFamily::Regular => self.run.channel_samples_count(index),
+            ChannelFamily::Gps => self.run.gps_channel_samples_count(index),
+            ChannelFamily::GpsRaw => self.run.gps_raw_channel_samples_count(index),
+        }
+        .unwrap_or(0)
+    }
+
+    fn lap_channel_samples(
+        &self,
+        family: ChannelFamily,
+        lap_index: usize,
+        index: usize,
+    ) -> Option<ChannelSamples> {
+        match family {
+            ChannelFamily::Regular => self.run.lap_channel_samples(lap_index, index),
+            ChannelFamily::Gps => self.run.lap_gps_channel_samples(lap_index, index),
+            ChannelFamily::GpsRaw => self.run.lap_gps_raw_channel_samples(lap_index, index),
+        }
+        .ok()
+        .map(into_samples)
+    }
+
+    fn lap_channel_samples_count(
+        &self,
+        family: ChannelFamily,
+        lap_index: usize,
+        index: usize,
+    ) -> usize {
+        match family {
+            ChannelFamily::Regular => self.run.lap_channel_samples_count(lap_index, index),
+            ChannelFamily::Gps => self.run.lap_gps_channel_samples_count(lap_index, index),
+            ChannelFamily::GpsRaw => self.run.lap_gps_raw_channel_samples_count(lap_index, index),
+        }
+        .unwrap_or(0)
+    }
+}
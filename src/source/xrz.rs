@@ -0,0 +1,76 @@
+//! Compressed sessions.
+//!
+//! Race Studio stores `.xrz` files as a zlib stream of the XRK file. Files are
+//! recognised by their magic bytes rather than their extension, so gzip'ed
+//! sessions are accepted as well.
+
+use flate2::read::{GzDecoder, ZlibDecoder};
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Zlib,
+    Gzip,
+}
+
+/// Detects the compression from the first bytes of a file.
+pub fn detect(bytes: &[u8]) -> Option<Compression> {
+    match bytes {
+        [0x1f, 0x8b, ..] => Some(Compression::Gzip),
+        // Deflate with a window of at most 32K and a valid header checksum.
+        [cmf, flg, ..]
+            if cmf & 0x0f == 8 && cmf >> 4 <= 7 && u16::from_be_bytes([*cmf, *flg]) % 31 == 0 =>
+        {
+            Some(Compression::Zlib)
+        }
+        _ => None,
+    }
+}
+
+/// Detects the compression of the file at `path` without reading all of it.
+pub fn detect_file(path: &Path) -> io::Result<Option<Compression>> {
+    let mut magic = Vec::with_capacity(2);
+    File::open(path)?.take(2).read_to_end(&mut magic)?;
+    Ok(detect(&magic))
+}
+
+/// Returns a reader over the decompressed contents of the file at `path`.
+pub fn open(path: &Path) -> io::Result<Box<dyn Read>> {
+    let compression = detect_file(path)?;
+    let file = File::open(path)?;
+
+    Ok(match compression {
+        Some(Compression::Zlib) => Box::new(ZlibDecoder::new(file)),
+        Some(Compression::Gzip) => Box::new(GzDecoder::new(file)),
+        None => Box::new(file),
+    })
+}
+
+/// Reads the file at `path` into memory, decompressing it if needed.
+pub fn read(path: &Path) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    open(path)?.read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::{GzEncoder, ZlibEncoder};
+    use std::io::Write;
+
+    #[test]
+    fn detects_compression_by_magic_bytes() {
+        let mut zlib = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        zlib.write_all(b"<hRCR").unwrap();
+        let mut gzip = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gzip.write_all(b"<hRCR").unwrap();
+
+        assert_eq!(detect(&zlib.finish().unwrap()), Some(Compression::Zlib));
+        assert_eq!(detect(&gzip.finish().unwrap()), Some(Compression::Gzip));
+        assert_eq!(detect(b"<hRCR"), None);
+        assert_eq!(detect(b""), None);
+    }
+}
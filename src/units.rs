@@ -0,0 +1,351 @@
+//! Unit conversion of channel values.
+//!
+//! Every known unit is defined by its dimension and a linear mapping onto the
+//! SI unit of that dimension, so any two units of the same dimension convert
+//! into each other.
+
+use crate::error::{Error, Result};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dimension {
+    Speed,
+    Pressure,
+    Temperature,
+    Distance,
+    Acceleration,
+}
+
+struct Unit {
+    /// The first symbol is the one used when converting into the unit.
+    symbols: &'static [&'static str],
+    dimension: Dimension,
+    /// `si = value * scale + offset`
+    scale: f64,
+    offset: f64,
+}
+
+const fn unit(symbols: &'static [&'static str], dimension: Dimension, scale: f64) -> Unit {
+    Unit {
+        symbols,
+        dimension,
+        scale,
+        offset: 0.0,
+    }
+}
+
+const UNITS: &[Unit] = &[
+    unit(&["m/s"], Dimension::Speed, 1.0),
+    unit(&["km/h", "kph", "kmh"], Dimension::Speed, 1.0 / 3.6),
+    unit(&["mph"], Dimension::Speed, 0.447_04),
+    unit(&["kn", "kt", "knot"], Dimension::Speed, 1852.0 / 3600.0),
+    unit(&["Pa"], Dimension::Pressure, 1.0),
+    unit(&["hPa"], Dimension::Pressure, 100.0),
+    unit(&["kPa"], Dimension::Pressure, 1_000.0),
+    unit(&["MPa"], Dimension::Pressure, 1_000_000.0),
+    unit(&["mbar"], Dimension::Pressure, 100.0),
+    unit(&["bar"], Dimension::Pressure, 100_000.0),
+    unit(&["psi"], Dimension::Pressure, 6_894.757_293_168),
+    Unit {
+        symbols: &["K"],
+        dimension: Dimension::Temperature,
+        scale: 1.0,
+        offset: 0.0,
+    },
+    Unit {
+        symbols: &["°C", "C", "degC", "deg C"],
+        dimension: Dimension::Temperature,
+        scale: 1.0,
+        offset: 273.15,
+    },
+    Unit {
+        symbols: &["°F", "F", "degF", "deg F"],
+        dimension: Dimension::Temperature,
+        scale: 5.0 / 9.0,
+        offset: 273.15 - 32.0 * 5.0 / 9.0,
+    },
+    unit(&["m"], Dimension::Distance, 1.0),
+    unit(&["mm"], Dimension::Distance, 0.001),
+    unit(&["cm"], Dimension::Distance, 0.01),
+    unit(&["km"], Dimension::Distance, 1_000.0),
+    unit(&["in"], Dimension::Distance, 0.0254),
+    unit(&["ft"], Dimension::Distance, 0.3048),
+    unit(&["mi"], Dimension::Distance, 1_609.344),
+    unit(&["m/s²", "m/s2", "m/s^2"], Dimension::Acceleration, 1.0),
+    unit(&["g", "G"], Dimension::Acceleration, 9.806_65),
+];
+
+fn find(symbol: &str) -> Option<&'static Unit> {
+    let symbol = symbol.trim();
+    UNITS
+        .iter()
+        .find(|unit| unit.symbols.contains(&symbol))
+        .or_else(|| {
+            UNITS.iter().find(|unit| {
+                unit.symbols
+                    .iter()
+                    .any(|candidate| candidate.eq_ignore_ascii_case(symbol))
+            })
+        })
+}
+
+/// The dimension of a unit symbol, if the unit is known.
+pub fn dimension(symbol: &str) -> Option<Dimension> {
+    find(symbol).map(|unit| unit.dimension)
+}
+
+/// A linear mapping from one unit onto another.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Conversion {
+    scale: f64,
+    offset: f64,
+}
+
+impl Conversion {
+    /// Conversion between two units of the same dimension.
+    pub fn between(from: &str, to: &str) -> Option<Self> {
+        let (from, to) = (find(from)?, find(to)?);
+        if from.dimension != to.dimension {
+            return None;
+        }
+
+        Some(Self {
+            scale: from.scale / to.scale,
+            offset: (from.offset - to.offset) / to.scale,
+        })
+    }
+
+    pub fn apply(&self, value: f64) -> f64 {
+        value * self.scale + self.offset
+    }
+}
+
+/// Converts `value` from one unit into another of the same dimension.
+pub fn convert(value: f64, from: &str, to: &str) -> Option<f64> {
+    Conversion::between(from, to).map(|conversion| conversion.apply(value))
+}
+
+/// A consistent set of units to present channels in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnitSystem {
+    /// km/h, bar, °C, m (mm for short lengths) and g.
+    Metric,
+    /// mph, psi, °F, ft (in for short lengths) and g.
+    Imperial,
+    /// m/s, Pa, K, m and m/s².
+    Si,
+}
+
+impl UnitSystem {
+    pub fn unit(&self, dimension: Dimension) -> &'static str {
+        match (self, dimension) {
+            (UnitSystem::Metric, Dimension::Speed) => "km/h",
+            (UnitSystem::Metric, Dimension::Pressure) => "bar",
+            (UnitSystem::Metric, Dimension::Temperature) => "°C",
+            (UnitSystem::Metric, Dimension::Distance) => "m",
+            (UnitSystem::Metric, Dimension::Acceleration) => "g",
+            (UnitSystem::Imperial, Dimension::Speed) => "mph",
+            (UnitSystem::Imperial, Dimension::Pressure) => "psi",
+            (UnitSystem::Imperial, Dimension::Temperature) => "°F",
+            (UnitSystem::Imperial, Dimension::Distance) => "ft",
+            (UnitSystem::Imperial, Dimension::Acceleration) => "g",
+            (UnitSystem::Si, Dimension::Speed) => "m/s",
+            (UnitSystem::Si, Dimension::Pressure) => "Pa",
+            (UnitSystem::Si, Dimension::Temperature) => "K",
+            (UnitSystem::Si, Dimension::Distance) => "m",
+            (UnitSystem::Si, Dimension::Acceleration) => "m/s²",
+        }
+    }
+
+    /// The unit of this system for values recorded in `unit`, if known.
+    ///
+    /// Short lengths (mm, cm and in, e.g. damper travel or ride height) are
+    /// kept in mm (metric) or in (imperial) rather than m or ft.
+    pub fn unit_for(&self, unit: &str) -> Option<&'static str> {
+        let unit = find(unit)?;
+        let short = matches!(unit.symbols[0], "mm" | "cm" | "in");
+
+        Some(match (self, short) {
+            (UnitSystem::Metric, true) => "mm",
+            (UnitSystem::Imperial, true) => "in",
+            _ => self.unit(unit.dimension),
+        })
+    }
+}
+
+impl std::str::FromStr for UnitSystem {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "metric" => Ok(UnitSystem::Metric),
+            "imperial" => Ok(UnitSystem::Imperial),
+            "si" => Ok(UnitSystem::Si),
+            _ => Err(Error::InvalidArgument(format!(
+                "unknown unit system '{}' (expected metric, imperial or si)",
+                s
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for UnitSystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            UnitSystem::Metric => "metric",
+            UnitSystem::Imperial => "imperial",
+            UnitSystem::Si => "si",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Which unit each channel is presented in.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UnitPreferences {
+    pub system: Option<UnitSystem>,
+    /// Unit per channel name, taking precedence over the system.
+    pub overrides: BTreeMap<String, String>,
+    /// Overridden channels the session must have, e.g. those given on the
+    /// command line; the other overrides only apply where the channel exists.
+    pub required: BTreeSet<String>,
+}
+
+impl UnitPreferences {
+    pub fn is_empty(&self) -> bool {
+        self.system.is_none() && self.overrides.is_empty()
+    }
+
+    /// The unit to present a channel in and the conversion into it, or `None`
+    /// to keep the channel as recorded.
+    ///
+    /// Fails if the channel has an override it cannot be converted into.
+    pub fn conversion(&self, channel: &str, unit: &str) -> Result<Option<(String, Conversion)>> {
+        let overridden = self
+            .overrides
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(channel))
+            .map(|(_, unit)| unit.as_str());
+
+        let target = match (
+            overridden,
+            self.system.and_then(|system| system.unit_for(unit)),
+        ) {
+            (Some(target), _) => target,
+            (None, Some(target)) => target,
+            _ => return Ok(None),
+        };
+
+        if target == unit {
+            return Ok(None);
+        }
+
+        match Conversion::between(unit, target) {
+            Some(conversion) => Ok(Some((target.to_string(), conversion))),
+            None => Err(Error::InvalidArgument(format!(
+                "cannot convert channel '{}' from '{}' to '{}'",
+                channel, unit, target
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_converts(value: f64, from: &str, to: &str, expected: f64) {
+        let converted = convert(value, from, to)
+            .unwrap_or_else(|| panic!("no conversion from {} to {}", from, to));
+        assert!(
+            (converted - expected).abs() < 1e-6 * expected.abs().max(1.0),
+            "{} {} = {} {}, expected {}",
+            value,
+            from,
+            converted,
+            to,
+            expected
+        );
+    }
+
+    #[test]
+    fn conversion_table() {
+        let table = [
+            // speed
+            (100.0, "km/h", "mph", 62.137_119),
+            (100.0, "km/h", "m/s", 27.777_778),
+            (10.0, "m/s", "km/h", 36.0),
+            (60.0, "mph", "km/h", 96.560_64),
+            (10.0, "kn", "km/h", 18.52),
+            // pressure
+            (1.0, "bar", "psi", 14.503_774),
+            (30.0, "psi", "bar", 2.068_427),
+            (1.0, "bar", "Pa", 100_000.0),
+            (250.0, "kPa", "bar", 2.5),
+            (1013.25, "mbar", "kPa", 101.325),
+            // temperature
+            (100.0, "°C", "°F", 212.0),
+            (-40.0, "C", "F", -40.0),
+            (0.0, "°C", "K", 273.15),
+            (98.6, "°F", "°C", 37.0),
+            // distance
+            (1.0, "km", "m", 1_000.0),
+            (1.0, "mi", "km", 1.609_344),
+            (100.0, "m", "ft", 328.083_99),
+            (12.0, "in", "cm", 30.48),
+            (25.4, "mm", "in", 1.0),
+            (1.5, "cm", "mm", 15.0),
+            // acceleration
+            (1.0, "g", "m/s²", 9.806_65),
+            (19.6133, "m/s2", "g", 2.0),
+        ];
+
+        for (value, from, to, expected) in table {
+            assert_converts(value, from, to, expected);
+        }
+    }
+
+    #[test]
+    fn short_lengths_stay_in_short_units() {
+        let cases = [
+            (UnitSystem::Metric, "mm", "mm"),
+            (UnitSystem::Metric, "cm", "mm"),
+            (UnitSystem::Metric, "ft", "m"),
+            (UnitSystem::Imperial, "mm", "in"),
+            (UnitSystem::Imperial, "km", "ft"),
+            (UnitSystem::Si, "mm", "m"),
+        ];
+
+        for (system, unit, expected) in cases {
+            assert_eq!(system.unit_for(unit), Some(expected), "{} {}", system, unit);
+        }
+        assert_eq!(UnitSystem::Metric.unit_for("rpm"), None);
+    }
+
+    #[test]
+    fn units_of_different_dimensions_do_not_convert() {
+        assert_eq!(convert(1.0, "bar", "km/h"), None);
+        assert_eq!(convert(1.0, "rpm", "rpm"), None);
+    }
+
+    #[test]
+    fn overrides_take_precedence_over_the_system() {
+        let preferences = UnitPreferences {
+            system: Some(UnitSystem::Imperial),
+            overrides: BTreeMap::from([("gps speed".to_string(), "km/h".to_string())]),
+            ..UnitPreferences::default()
+        };
+
+        assert_eq!(preferences.conversion("GPS Speed", "km/h").unwrap(), None);
+        let (unit, _) = preferences.conversion("Oil Temp", "°C").unwrap().unwrap();
+        assert_eq!(unit, "°F");
+        assert_eq!(preferences.conversion("RPM", "rpm").unwrap(), None);
+
+        let wrong = UnitPreferences {
+            overrides: BTreeMap::from([("Oil Temp".to_string(), "psi".to_string())]),
+            ..UnitPreferences::default()
+        };
+        assert!(wrong.conversion("Oil Temp", "°C").is_err());
+    }
+}
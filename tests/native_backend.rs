@@ -0,0 +1,112 @@
+//! Compares the native decoder against `libmatlabxrk` on a real file:
+//!
+//! `XRK_TEST_FILE=session.xrk cargo test --features native`
+//!
+//! Without `XRK_TEST_FILE` there is nothing to compare and the test passes.
+//!
+//! Regular channels must match one for one. Of the GPS and GPS raw channels
+//! the native decoder only has `GPS Speed` and the ECEF positions and
+//! velocities, which are compared with the `libmatlabxrk` channels of the
+//! same name.
+
+#![cfg(all(feature = "native", feature = "xdrk"))]
+
+use std::path::Path;
+use xrk_cli::source::{native, XdrkSource};
+use xrk_cli::{ChannelFamily, TelemetrySource};
+
+fn assert_close(actual: &[f64], expected: &[f64], what: &str) {
+    assert_eq!(actual.len(), expected.len(), "{}: length", what);
+    for (i, (a, e)) in actual.iter().zip(expected).enumerate() {
+        assert!((a - e).abs() < 1e-3, "{} [{}]: {} != {}", what, i, a, e);
+    }
+}
+
+#[test]
+fn native_backend_matches_xdrk_backend() {
+    let Ok(path) = std::env::var("XRK_TEST_FILE") else {
+        eprintln!("XRK_TEST_FILE not set, skipping");
+        return;
+    };
+    let path = Path::new(&path);
+    let native = native::load(path).unwrap();
+    let xdrk = XdrkSource::load(path).unwrap();
+
+    assert_eq!(native.racer(), xdrk.racer());
+    assert_eq!(native.vehicle(), xdrk.vehicle());
+    assert_eq!(native.track(), xdrk.track());
+    assert_eq!(native.datetime(), xdrk.datetime());
+
+    assert_eq!(native.number_of_laps(), xdrk.number_of_laps());
+    for lap in 0..xdrk.number_of_laps() {
+        let (expected, actual) = (xdrk.lap_info(lap).unwrap(), native.lap_info(lap).unwrap());
+        assert_close(
+            &[actual.start(), actual.time()],
+            &[expected.start(), expected.time()],
+            &format!("lap {} start and time", lap + 1),
+        );
+    }
+
+    eprintln!(
+        "Comparing {} ({:?}, {:?}, {:?})",
+        path.display(),
+        xdrk.vehicle(),
+        xdrk.track(),
+        xdrk.datetime()
+    );
+
+    let family = ChannelFamily::Regular;
+    assert_eq!(
+        native.channels_count(family),
+        xdrk.channels_count(family),
+        "{} channels",
+        family
+    );
+    for index in 0..xdrk.channels_count(family) {
+        assert_eq!(
+            native.channel_name(family, index),
+            xdrk.channel_name(family, index)
+        );
+        assert_channel(&native, index, &xdrk, index, family);
+    }
+
+    for family in [ChannelFamily::Gps, ChannelFamily::GpsRaw] {
+        for index in 0..native.channels_count(family) {
+            let name = native.channel_name(family, index);
+            let xdrk_index = (0..xdrk.channels_count(family))
+                .find(|&i| xdrk.channel_name(family, i) == name)
+                .unwrap_or_else(|| panic!("{} {:?} is missing from xdrk", family, name));
+            assert_channel(&native, index, &xdrk, xdrk_index, family);
+        }
+    }
+}
+
+fn assert_channel(
+    native: &dyn TelemetrySource,
+    index: usize,
+    xdrk: &dyn TelemetrySource,
+    xdrk_index: usize,
+    family: ChannelFamily,
+) {
+    let name = xdrk.channel_name(family, xdrk_index);
+    assert_eq!(
+        native.channel_unit(family, index),
+        xdrk.channel_unit(family, xdrk_index),
+        "{:?}",
+        name
+    );
+
+    let expected = xdrk.channel_samples(family, xdrk_index).unwrap_or_default();
+    let actual = native.channel_samples(family, index).unwrap_or_default();
+    let what = format!("{} {:?}", family, name);
+    assert_close(
+        actual.timestamps(),
+        expected.timestamps(),
+        &format!("{} timestamps", what),
+    );
+    assert_close(
+        actual.samples(),
+        expected.samples(),
+        &format!("{} samples", what),
+    );
+}